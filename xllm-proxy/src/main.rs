@@ -70,12 +70,17 @@ async fn handle_client(mut stream: TcpStream) -> Result<()> {
 
     println!("🔄 Decrypted request: {} {}", http_request.method, http_request.url);
 
-    // Execute the actual HTTP request
+    // Execute the actual HTTP request. Upstream failures are reported as a
+    // 502 so the client can tell them apart from a broken proxy and retry.
     let http_response = match execute_http_request(http_request).await {
         Ok(resp) => resp,
         Err(e) => {
             println!("❌ HTTP request failed: {}", e);
-            return Ok(());
+            HttpResponse {
+                status_code: 502,
+                headers: HashMap::new(),
+                body: format!("Proxy could not reach upstream: {}", e).into_bytes(),
+            }
         }
     };
 
@@ -201,11 +206,14 @@ fn is_generic_header(header_name: &str) -> bool {
         "date" | "server" | "connection" | "keep-alive" => true,
         "strict-transport-security" | "x-content-type-options" => true,
         "x-frame-options" | "x-xss-protection" => true,
+
+        // Rate limit headers are needed by the client to back off and retry
+        "retry-after" | "retry-after-ms" => true,
+        header if header.starts_with("x-ratelimit") => true,
         
         // Block provider-specific headers that expose the backend service
         header if header.starts_with("anthropic-") => false,
         header if header.starts_with("openai-") => false,
-        header if header.starts_with("x-ratelimit") => false,
         header if header.starts_with("x-request-id") => false,
         "request-id" | "cf-ray" | "cf-cache-status" => false,
         "via" | "x-robots-tag" => false,
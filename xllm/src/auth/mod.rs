@@ -0,0 +1,325 @@
+use crate::genconfig::resolve_env_variables;
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Read};
+use std::process::{Command, Stdio};
+
+/// Service name secrets are stored under in the OS keyring
+const KEYRING_SERVICE: &str = "xllm";
+
+/// Where `keyring:` secrets are kept. Tests use a JSON file instead of the
+/// OS keyring.
+enum SecretStore {
+    Keyring,
+    #[cfg(test)]
+    File(std::path::PathBuf),
+}
+
+impl SecretStore {
+    fn describe(&self) -> String {
+        match self {
+            SecretStore::Keyring => "the OS keyring".to_string(),
+            #[cfg(test)]
+            SecretStore::File(path) => path.display().to_string(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        let secret = match self {
+            SecretStore::Keyring => match keyring_entry(name)?.get_password() {
+                Ok(secret) => Some(secret),
+                Err(keyring::Error::NoEntry) => None,
+                Err(e) => {
+                    return Err(anyhow::Error::new(e)
+                        .context(format!("Failed to read {} from the OS keyring", name)));
+                }
+            },
+            #[cfg(test)]
+            SecretStore::File(path) => read_store_file(path)?.remove(name),
+        };
+        secret.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No secret named {} in {} (add it with `xllm auth set {}`)",
+                name,
+                self.describe(),
+                name
+            )
+        })
+    }
+
+    fn set(&self, name: &str, secret: &str) -> Result<()> {
+        match self {
+            SecretStore::Keyring => keyring_entry(name)?
+                .set_password(secret)
+                .with_context(|| format!("Failed to store {} in the OS keyring", name)),
+            #[cfg(test)]
+            SecretStore::File(path) => {
+                let mut secrets = read_store_file(path)?;
+                secrets.insert(name.to_string(), secret.to_string());
+                write_store_file(path, &secrets)
+            }
+        }
+    }
+
+    /// Delete a secret, returning whether it existed
+    fn remove(&self, name: &str) -> Result<bool> {
+        match self {
+            SecretStore::Keyring => match keyring_entry(name)?.delete_credential() {
+                Ok(()) => Ok(true),
+                Err(keyring::Error::NoEntry) => Ok(false),
+                Err(e) => Err(anyhow::Error::new(e)
+                    .context(format!("Failed to remove {} from the OS keyring", name))),
+            },
+            #[cfg(test)]
+            SecretStore::File(path) => {
+                let mut secrets = read_store_file(path)?;
+                let existed = secrets.remove(name).is_some();
+                write_store_file(path, &secrets)?;
+                Ok(existed)
+            }
+        }
+    }
+}
+
+fn keyring_entry(name: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .with_context(|| format!("Failed to open OS keyring entry for {}", name))
+}
+
+#[cfg(test)]
+fn read_store_file(path: &std::path::Path) -> Result<std::collections::BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(Default::default());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret store: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse secret store: {}", path.display()))
+}
+
+#[cfg(test)]
+fn write_store_file(
+    path: &std::path::Path,
+    secrets: &std::collections::BTreeMap<String, String>,
+) -> Result<()> {
+    let content = serde_json::to_string_pretty(secrets).context("Failed to serialize secrets")?;
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write secret store: {}", path.display()))
+}
+
+/// Resolve a credential from the config: `keyring:<name>` reads the OS keyring,
+/// `cmd:<command>` runs a shell command and uses its output, and anything else
+/// has its `${VAR}`s replaced from the environment
+pub fn resolve_secret(value: &str) -> Result<String> {
+    resolve_secret_in(&SecretStore::Keyring, value)
+}
+
+fn resolve_secret_in(store: &SecretStore, value: &str) -> Result<String> {
+    if let Some(name) = value.strip_prefix("keyring:") {
+        store.get(name.trim())
+    } else if let Some(command) = value.strip_prefix("cmd:") {
+        run_secret_command(command.trim())
+    } else {
+        Ok(resolve_env_variables(value))
+    }
+}
+
+/// Whether a credential says where to find the secret (`keyring:`, `cmd:` or a
+/// lone `${VAR}`) rather than holding it
+pub fn is_secret_reference(value: &str) -> bool {
+    let placeholder = value
+        .strip_prefix("${")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .is_some_and(|name| !name.is_empty() && !name.contains(['{', '}']));
+    value.starts_with("keyring:") || value.starts_with("cmd:") || placeholder
+}
+
+/// Run a credential helper such as `pass show anthropic`. Its stdin is closed so
+/// it can't swallow a piped prompt; helpers that ask for a passphrase do so on
+/// /dev/tty, as pass and gpg already do. Only the first line of output is used.
+fn run_secret_command(command: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run credential command: {}", command))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Credential command `{}` failed with {}",
+            command,
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("Credential command `{}` printed invalid UTF-8", command))?;
+    let secret = stdout.lines().next().unwrap_or_default().trim().to_string();
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Credential command `{}` printed nothing",
+            command
+        ));
+    }
+    Ok(secret)
+}
+
+/// Store a secret for use as `keyring:<name>`. It is read from stdin when
+/// piped, otherwise prompted for without echo.
+pub fn set_secret(name: &str) -> Result<()> {
+    let secret = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("🔑 Secret for {}: ", name))
+            .context("Failed to read secret")?
+    } else {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read secret from stdin")?;
+        input
+    };
+    store_secret(&SecretStore::Keyring, name, &secret)?;
+    eprintln!("   Use it in config.toml as \"keyring:{}\"", name);
+    Ok(())
+}
+
+fn store_secret(store: &SecretStore, name: &str, secret: &str) -> Result<()> {
+    let secret = secret.trim();
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("No secret given for {}", name));
+    }
+    store.set(name, secret)?;
+    eprintln!("🔑 Stored {} in {}", name, store.describe());
+    Ok(())
+}
+
+/// Delete a secret stored with `set_secret`
+pub fn remove_secret(name: &str) -> Result<()> {
+    remove_stored_secret(&SecretStore::Keyring, name)
+}
+
+fn remove_stored_secret(store: &SecretStore, name: &str) -> Result<()> {
+    if !store.remove(name)? {
+        return Err(anyhow::anyhow!(
+            "No secret named {} in {}",
+            name,
+            store.describe()
+        ));
+    }
+    eprintln!("🗑️  Removed {} from {}", name, store.describe());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh file store for one test
+    fn temp_store(test: &str) -> SecretStore {
+        let path =
+            std::env::temp_dir().join(format!("xllm-secrets-{}-{}.json", std::process::id(), test));
+        let _ = std::fs::remove_file(&path);
+        SecretStore::File(path)
+    }
+
+    #[test]
+    fn stored_secret_resolves_as_keyring_reference() {
+        let store = temp_store("resolve");
+        store_secret(&store, "anthropic", "  sk-ant-123\n").unwrap();
+        assert_eq!(
+            resolve_secret_in(&store, "keyring:anthropic").unwrap(),
+            "sk-ant-123"
+        );
+        assert_eq!(
+            resolve_secret_in(&store, "keyring: anthropic ").unwrap(),
+            "sk-ant-123"
+        );
+    }
+
+    #[test]
+    fn set_replaces_an_existing_secret() {
+        let store = temp_store("replace");
+        store_secret(&store, "openai", "old").unwrap();
+        store_secret(&store, "openai", "new").unwrap();
+        assert_eq!(resolve_secret_in(&store, "keyring:openai").unwrap(), "new");
+    }
+
+    #[test]
+    fn empty_secret_is_rejected() {
+        let store = temp_store("empty");
+        assert!(store_secret(&store, "anthropic", " \n").is_err());
+        assert!(resolve_secret_in(&store, "keyring:anthropic").is_err());
+    }
+
+    #[test]
+    fn removed_secret_no_longer_resolves() {
+        let store = temp_store("remove");
+        store_secret(&store, "anthropic", "sk-ant-123").unwrap();
+        store_secret(&store, "openai", "sk-456").unwrap();
+        remove_stored_secret(&store, "anthropic").unwrap();
+
+        let error = resolve_secret_in(&store, "keyring:anthropic").unwrap_err();
+        assert!(error.to_string().contains("xllm auth set anthropic"));
+        assert_eq!(
+            resolve_secret_in(&store, "keyring:openai").unwrap(),
+            "sk-456"
+        );
+        assert!(remove_stored_secret(&store, "anthropic").is_err());
+    }
+
+    #[test]
+    fn plain_values_resolve_env_variables() {
+        let store = temp_store("plain");
+        assert_eq!(
+            resolve_secret_in(&store, "sk-literal").unwrap(),
+            "sk-literal"
+        );
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(resolve_secret_in(&store, "${PATH}").unwrap(), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_uses_first_line_of_output() {
+        let store = temp_store("cmd");
+        assert_eq!(
+            resolve_secret_in(&store, "cmd: printf 'sk-cmd \\nsecond line\\n'").unwrap(),
+            "sk-cmd"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_does_not_read_stdin() {
+        let store = temp_store("stdin");
+        assert_eq!(
+            resolve_secret_in(&store, "cmd:cat; echo done").unwrap(),
+            "done"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_or_silent_command_is_an_error() {
+        let store = temp_store("cmd-error");
+        assert!(resolve_secret_in(&store, "cmd:exit 3").is_err());
+        assert!(resolve_secret_in(&store, "cmd:true").is_err());
+    }
+
+    #[test]
+    fn references_are_told_apart_from_secrets() {
+        assert!(is_secret_reference("keyring:anthropic"));
+        assert!(is_secret_reference("cmd:pass show openai"));
+        assert!(is_secret_reference("${ANTHROPIC_API_KEY}"));
+        assert!(!is_secret_reference("sk-ant-123"));
+        assert!(!is_secret_reference("Bearer ${TOKEN}"));
+        assert!(!is_secret_reference("${}"));
+    }
+}
@@ -0,0 +1,474 @@
+use crate::genconfig::Config;
+use crate::session::format_age;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+use crate::utils::attachments::format_size;
+use crate::utils::date::unix_now;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 86_400;
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Headers left out of the cache key so keys do not depend on credentials
+const SECRET_HEADERS: &[&str] = &["authorization", "x-api-key"];
+
+const STATS_FILENAME: &str = "stats.json";
+
+/// How a single run uses the cache, from `--no-cache` and `--refresh`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Serve hits and store misses
+    Use,
+    /// Skip lookups but store the fresh response
+    Refresh,
+    /// Neither read nor write
+    Off,
+}
+
+impl CacheMode {
+    pub fn from_flags(no_cache: bool, refresh: bool) -> Self {
+        if no_cache {
+            CacheMode::Off
+        } else if refresh {
+            CacheMode::Refresh
+        } else {
+            CacheMode::Use
+        }
+    }
+}
+
+/// A stored response body and when it was fetched
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp (seconds) of the original request
+    created_at: u64,
+    url: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+/// Hit and miss counters kept next to the entries
+#[derive(Serialize, Deserialize, Default)]
+struct CacheCounters {
+    hits: u64,
+    misses: u64,
+}
+
+/// Cached responses under the XDG cache directory, one file per request hash
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl_secs: u64,
+    max_bytes: u64,
+}
+
+impl ResponseCache {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(ResponseCache {
+            dir: get_cache_dir()?,
+            ttl_secs: config.cache.ttl_secs,
+            max_bytes: config.cache.max_bytes,
+        })
+    }
+
+    /// Hash of everything that determines the response: method, URL,
+    /// non-secret headers and body
+    fn key(request: &HttpRequest) -> String {
+        // Header names are case-insensitive, so they are lowercased before sorting
+        let mut headers: Vec<_> = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), value))
+            .filter(|(name, _)| !SECRET_HEADERS.contains(&name.as_str()))
+            .collect();
+        headers.sort();
+
+        let mut hasher = Sha256::new();
+        hasher.update(request.method.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(request.url.as_bytes());
+        hasher.update(b"\n");
+        for (name, value) in headers {
+            hasher.update(format!("{}: {}\n", name, value).as_bytes());
+        }
+        hasher.update(b"\n");
+        hasher.update(&request.body);
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// A fresh entry for the request, if there is one
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let path = self.entry_path(key);
+        let content = fs::read_to_string(&path).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        if unix_now().saturating_sub(entry.created_at) > self.ttl_secs {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn put(&self, key: &str, request: &HttpRequest, response: &HttpResponse) -> Result<()> {
+        // Responses are JSON or server-sent events; anything else is not cached
+        let Ok(body) = String::from_utf8(response.body.clone()) else {
+            return Ok(());
+        };
+        let entry = CacheEntry {
+            created_at: unix_now(),
+            url: request.url.clone(),
+            headers: response.headers.clone(),
+            body,
+        };
+
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!("Failed to create cache directory: {}", self.dir.display())
+        })?;
+        let path = self.entry_path(key);
+        let content = serde_json::to_string(&entry).context("Failed to serialize cache entry")?;
+        fs::write(&path, content)
+            .with_context(|| format!("Failed to write cache entry: {}", path.display()))?;
+        self.prune()
+    }
+
+    /// Remove expired entries, then the oldest ones until the cache fits in `max_bytes`
+    fn prune(&self) -> Result<()> {
+        let now = unix_now();
+        let mut entries = Vec::new();
+        for file in list_entries(&self.dir)? {
+            if now.saturating_sub(file.created_at) > self.ttl_secs {
+                let _ = fs::remove_file(&file.path);
+            } else {
+                entries.push(file);
+            }
+        }
+
+        entries.sort_by_key(|file| file.created_at);
+        let mut total: u64 = entries.iter().map(|file| file.size).sum();
+        for file in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&file.path).is_ok() {
+                total -= file.size;
+            }
+        }
+        Ok(())
+    }
+
+    fn count(&self, hit: bool) {
+        let path = self.dir.join(STATS_FILENAME);
+        let mut counters: CacheCounters = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        if hit {
+            counters.hits += 1;
+        } else {
+            counters.misses += 1;
+        }
+        if fs::create_dir_all(&self.dir).is_ok()
+            && let Ok(content) = serde_json::to_string(&counters)
+        {
+            let _ = fs::write(&path, content);
+        }
+    }
+}
+
+/// Serves repeated requests from a `ResponseCache` instead of the wrapped
+/// transport. Only POST requests (completions and token counts) are cached.
+pub struct CachingTransport {
+    inner: Box<dyn Transport>,
+    cache: ResponseCache,
+    mode: CacheMode,
+    last_hit: AtomicBool,
+}
+
+impl CachingTransport {
+    pub fn new(inner: Box<dyn Transport>, cache: ResponseCache, mode: CacheMode) -> Self {
+        CachingTransport {
+            inner,
+            cache,
+            mode,
+            last_hit: AtomicBool::new(false),
+        }
+    }
+
+    /// Look the request up, recording whether it was a hit
+    fn lookup(&self, request: &HttpRequest) -> (String, Option<CacheEntry>) {
+        let key = ResponseCache::key(request);
+        let entry = match self.mode {
+            CacheMode::Use => self.cache.get(&key),
+            CacheMode::Refresh | CacheMode::Off => None,
+        };
+        self.last_hit.store(entry.is_some(), Ordering::Relaxed);
+        self.cache.count(entry.is_some());
+        if let Some(entry) = &entry {
+            eprintln!(
+                "💾 Using cached response from {} (--refresh to fetch a new one)",
+                format_age(entry.created_at)
+            );
+        }
+        (key, entry)
+    }
+
+    fn store(&self, key: &str, request: &HttpRequest, response: &HttpResponse) {
+        if let Err(e) = self.cache.put(key, request, response) {
+            eprintln!("⚠️  Failed to cache response: {}", e);
+        }
+    }
+
+    fn bypass(&self, request: &HttpRequest) -> bool {
+        self.last_hit.store(false, Ordering::Relaxed);
+        self.mode == CacheMode::Off || request.method != "POST"
+    }
+}
+
+#[async_trait]
+impl Transport for CachingTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        if self.bypass(request) {
+            return self.inner.send(request).await;
+        }
+
+        let (key, entry) = self.lookup(request);
+        if let Some(entry) = entry {
+            return Ok(HttpResponse {
+                status_code: 200,
+                headers: entry.headers,
+                body: entry.body.into_bytes(),
+            });
+        }
+
+        let response = self.inner.send(request).await?;
+        self.store(&key, request, &response);
+        Ok(response)
+    }
+
+    async fn send_streaming(
+        &self,
+        request: &HttpRequest,
+        on_chunk: &mut (dyn for<'a> FnMut(&'a [u8]) -> Result<()> + Send),
+    ) -> Result<()> {
+        if self.bypass(request) {
+            return self.inner.send_streaming(request, on_chunk).await;
+        }
+
+        // A cached stream is replayed as a single chunk
+        let (key, entry) = self.lookup(request);
+        if let Some(entry) = entry {
+            return on_chunk(entry.body.as_bytes());
+        }
+
+        let mut body = Vec::new();
+        self.inner
+            .send_streaming(request, &mut |chunk| {
+                body.extend_from_slice(chunk);
+                on_chunk(chunk)
+            })
+            .await?;
+        let response = HttpResponse {
+            status_code: 200,
+            headers: HashMap::new(),
+            body,
+        };
+        self.store(&key, request, &response);
+        Ok(())
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn served_from_cache(&self) -> bool {
+        self.last_hit.load(Ordering::Relaxed)
+    }
+}
+
+/// Response cache directory (~/.cache/xllm/responses)
+pub fn get_cache_dir() -> Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|p| p.join("xllm").join("responses"))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine cache directory"))
+}
+
+/// A cache entry on disk
+struct EntryFile {
+    path: PathBuf,
+    size: u64,
+    created_at: u64,
+}
+
+fn list_entries(dir: &Path) -> Result<Vec<EntryFile>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Failed to read cache directory: {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let is_entry = path.extension().is_some_and(|ext| ext == "json")
+            && path.file_name().is_some_and(|name| name != STATS_FILENAME);
+        if !is_entry {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        // Unreadable entries sort first so they are pruned early
+        let created_at = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+            .map(|entry| entry.created_at)
+            .unwrap_or_default();
+        entries.push(EntryFile {
+            path,
+            size: metadata.len(),
+            created_at,
+        });
+    }
+    Ok(entries)
+}
+
+/// Delete every cached response and the hit counters
+pub fn clear_cache() -> Result<()> {
+    let dir = get_cache_dir()?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove cache directory: {}", dir.display()))?;
+    }
+    println!("🧹 Cleared response cache at {}", dir.display());
+    Ok(())
+}
+
+/// Print the size and hit rate of the cache
+pub fn print_stats(config: &Config) -> Result<()> {
+    let dir = get_cache_dir()?;
+    let entries = list_entries(&dir)?;
+    let now = unix_now();
+    let expired = entries
+        .iter()
+        .filter(|entry| now.saturating_sub(entry.created_at) > config.cache.ttl_secs)
+        .count();
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let counters: CacheCounters = fs::read_to_string(dir.join(STATS_FILENAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let lookups = counters.hits + counters.misses;
+
+    println!("Location:  {}", dir.display());
+    println!(
+        "Enabled:   {}",
+        if config.cache.enabled { "yes" } else { "no (set enabled = true in [cache])" }
+    );
+    println!(
+        "Entries:   {} ({} expired)",
+        entries.len(),
+        expired
+    );
+    println!(
+        "Size:      {} of {}",
+        format_size(total as usize),
+        format_size(config.cache.max_bytes as usize)
+    );
+    println!("TTL:       {}s", config.cache.ttl_secs);
+    if lookups > 0 {
+        println!(
+            "Hits:      {} of {} lookups ({:.0}%)",
+            counters.hits,
+            lookups,
+            counters.hits as f64 * 100.0 / lookups as f64
+        );
+    } else {
+        println!("Hits:      no lookups yet");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)], body: &str) -> HttpRequest {
+        HttpRequest {
+            method: "POST".to_string(),
+            url: "https://api.anthropic.com/v1/messages".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn key_is_stable_across_runs() {
+        // Changing this hash would orphan every existing cache entry
+        let key = ResponseCache::key(&request(
+            &[
+                ("anthropic-version", "2023-06-01"),
+                ("content-type", "application/json"),
+            ],
+            r#"{"model":"claude-sonnet-4-5"}"#,
+        ));
+        assert_eq!(
+            key,
+            "59ab4bf6946b06fafa443fbeeb71842993d2820df20532ec508aeb2930848868"
+        );
+    }
+
+    #[test]
+    fn key_ignores_header_order_case_and_credentials() {
+        let key = ResponseCache::key(&request(
+            &[
+                ("x-api-key", "sk-one"),
+                ("b-header", "1"),
+                ("A-Header", "2"),
+            ],
+            "{}",
+        ));
+        let same = ResponseCache::key(&request(
+            &[
+                ("a-header", "2"),
+                ("B-Header", "1"),
+                ("X-Api-Key", "sk-two"),
+            ],
+            "{}",
+        ));
+        let without_key =
+            ResponseCache::key(&request(&[("b-header", "1"), ("a-header", "2")], "{}"));
+        assert_eq!(key, same);
+        assert_eq!(key, without_key);
+    }
+
+    #[test]
+    fn key_changes_with_method_url_headers_and_body() {
+        let base = request(&[("anthropic-version", "2023-06-01")], "{}");
+        let key = ResponseCache::key(&base);
+
+        let mut other = request(&[("anthropic-version", "2023-06-01")], "{}");
+        other.method = "GET".to_string();
+        assert_ne!(ResponseCache::key(&other), key);
+
+        let mut other = request(&[("anthropic-version", "2023-06-01")], "{}");
+        other.url.push_str("?beta=true");
+        assert_ne!(ResponseCache::key(&other), key);
+
+        let other = request(&[("anthropic-version", "2024-01-01")], "{}");
+        assert_ne!(ResponseCache::key(&other), key);
+
+        let other = request(&[("anthropic-version", "2023-06-01")], "{ }");
+        assert_ne!(ResponseCache::key(&other), key);
+    }
+}
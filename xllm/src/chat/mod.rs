@@ -0,0 +1,296 @@
+use crate::cache::CacheMode;
+use crate::genconfig::{Config, get_model_config, supported_models};
+use crate::models::{LlmProvider, Message, provider_for};
+use crate::session::Session;
+use crate::tools::{Interrupted, send_with_tools, tool_definitions};
+use crate::transport::{Timeouts, Transport, transport_for};
+use crate::usage::record_usage;
+use crate::utils::context::{CollectedContext, ContextBudget, collect_files};
+use crate::utils::render::{new_spinner, render_markdown};
+use anyhow::{Context, Result};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const CHAT_HELP: &str = "\
+Commands:
+  /model [NAME]  Show or switch the model (any name -m accepts)
+  /clear         Start a new conversation
+  /file PATH     Attach files (path, directory or glob) to your next message
+  /save PATH     Save the conversation (.json for raw messages, otherwise markdown)
+  /help          Show this help
+  /exit          Leave the chat (Ctrl-D also works)";
+
+/// State carried across turns of an interactive chat
+struct ChatSession {
+    model_name: String,
+    provider: Box<dyn LlmProvider>,
+    /// Built per model, since timeouts can be set per model
+    transport: Box<dyn Transport>,
+    conversation: Session,
+    attachments: Vec<CollectedContext>,
+    /// `--system` override, kept across /model switches
+    system: Option<String>,
+    /// Whether `--tools` was given
+    tools: bool,
+    cache_mode: CacheMode,
+}
+
+impl ChatSession {
+    fn new(
+        config: &Config,
+        model_str: Option<&str>,
+        system: Option<String>,
+        tools: bool,
+        cache_mode: CacheMode,
+        conversation: Session,
+    ) -> Result<Self> {
+        let (provider, transport) =
+            chat_provider(config, model_str, system.as_deref(), tools, cache_mode)?;
+        Ok(ChatSession {
+            model_name: model_str.unwrap_or(provider.model()).to_string(),
+            provider,
+            transport,
+            conversation,
+            attachments: Vec::new(),
+            system,
+            tools,
+            cache_mode,
+        })
+    }
+}
+
+fn chat_provider(
+    config: &Config,
+    model_str: Option<&str>,
+    system: Option<&str>,
+    tools: bool,
+    cache_mode: CacheMode,
+) -> Result<(Box<dyn LlmProvider>, Box<dyn Transport>)> {
+    let mut model_provider = get_model_config(config, model_str)?;
+    if let Some(system) = system {
+        model_provider.override_system(system.to_string());
+    }
+    if tools {
+        model_provider.set_tools(tool_definitions(config)?)?;
+    }
+    let transport = transport_for(
+        config,
+        Timeouts::for_model(config, &model_provider),
+        cache_mode,
+    )?;
+    Ok((provider_for(model_provider)?, transport))
+}
+
+/// Command line options that apply to every turn of a chat
+pub struct ChatOptions {
+    pub max_tokens_override: Option<u32>,
+    pub system: Option<String>,
+    pub tools: bool,
+    pub stream: bool,
+    /// Print token counts and cost after each reply
+    pub show_usage: bool,
+    pub cache_mode: CacheMode,
+}
+
+/// Run an interactive multi-turn chat until the user exits.
+/// Each exchange is appended to `conversation` and saved to disk.
+pub async fn run_chat(
+    config: &Config,
+    model_str: Option<&str>,
+    options: ChatOptions,
+    conversation: Session,
+) -> Result<()> {
+    let mut session = ChatSession::new(
+        config,
+        model_str,
+        options.system,
+        options.tools,
+        options.cache_mode,
+        conversation,
+    )?;
+    let mut editor = DefaultEditor::new().context("Failed to initialise line editor")?;
+
+    println!(
+        "💬 Chatting with {} — type /help for commands, /exit to quit",
+        session.model_name
+    );
+    if !session.conversation.messages.is_empty() {
+        println!(
+            "📂 Resumed session {} ({} messages)",
+            session.conversation.id,
+            session.conversation.messages.len()
+        );
+    }
+
+    loop {
+        let line = match editor.readline("❯ ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                println!("(use /exit or Ctrl-D to quit)");
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e).context("Failed to read input"),
+        };
+
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(input);
+
+        if let Some(command) = input.strip_prefix('/') {
+            if !handle_command(config, &mut session, command) {
+                break;
+            }
+            continue;
+        }
+
+        let mut prompt = input.to_string();
+        for context in session.attachments.drain(..) {
+            prompt = context.append_to(&prompt);
+        }
+        let turn_start = session.conversation.messages.len();
+        session.conversation.messages.push(Message::user(prompt));
+
+        let spinner = new_spinner();
+        let streamed = options.stream && session.transport.supports_streaming();
+        let mut on_text = |text: &str| {
+            if !spinner.is_finished() {
+                spinner.finish_and_clear();
+            }
+            print!("{}", text);
+            let _ = std::io::stdout().flush();
+        };
+        let send = send_with_tools(
+            session.provider.as_ref(),
+            session.transport.as_ref(),
+            &config.tools,
+            &mut session.conversation.messages,
+            options.max_tokens_override,
+            streamed,
+            &mut on_text,
+        );
+        // Ctrl-C cancels the reply, dropping its connection, and returns to the prompt
+        let result = tokio::select! {
+            result = send => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        // Ctrl-C at a tool confirmation is a keypress, not a signal
+        let result = result.filter(|result| !matches!(result, Err(e) if e.is::<Interrupted>()));
+        spinner.finish_and_clear();
+
+        let Some(result) = result else {
+            if streamed {
+                println!();
+            }
+            session.conversation.messages.truncate(turn_start);
+            eprintln!("⚠️  Interrupted");
+            continue;
+        };
+
+        match result {
+            Ok(reply) => {
+                if streamed {
+                    println!();
+                } else {
+                    render_markdown(&reply.text);
+                    println!();
+                }
+                record_usage(
+                    config,
+                    &reply,
+                    session.provider.model(),
+                    options.show_usage,
+                    session.transport.served_from_cache(),
+                );
+                session.conversation.record(&reply);
+                if let Err(e) = session.conversation.save() {
+                    eprintln!("⚠️  Failed to save session: {}", e);
+                }
+            }
+            Err(e) => {
+                // Drop the unanswered turn so the history stays alternating
+                session.conversation.messages.truncate(turn_start);
+                eprintln!("❌ Error: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a slash command. Returns `false` when the chat should end.
+fn handle_command(config: &Config, session: &mut ChatSession, command: &str) -> bool {
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+
+    match name {
+        "exit" | "quit" => return false,
+        "help" => {
+            println!("{}", CHAT_HELP);
+            println!("\nModels: {}", supported_models(Some(config)));
+        }
+        "clear" => {
+            session.conversation = Session::new();
+            session.attachments.clear();
+            println!("🧹 Conversation cleared");
+        }
+        "model" if arg.is_empty() => println!("🤖 Current model: {}", session.model_name),
+        "model" => match chat_provider(
+            config,
+            Some(arg),
+            session.system.as_deref(),
+            session.tools,
+            session.cache_mode,
+        ) {
+            Ok((provider, transport)) => {
+                session.model_name = arg.to_string();
+                session.provider = provider;
+                session.transport = transport;
+                println!("🤖 Switched to {}", arg);
+            }
+            Err(e) => eprintln!("❌ {}", e),
+        },
+        "file" if arg.is_empty() => eprintln!("❌ Usage: /file PATH"),
+        "file" => match collect_files(&[arg.to_string()], ContextBudget::from_config(config)) {
+            Ok(context) => {
+                context.print_report();
+                session.attachments.push(context);
+            }
+            Err(e) => eprintln!("❌ {}", e),
+        },
+        "save" if arg.is_empty() => eprintln!("❌ Usage: /save PATH"),
+        "save" => match save_transcript(&session.conversation.messages, Path::new(arg)) {
+            Ok(()) => println!(
+                "💾 Saved {} messages to {}",
+                session.conversation.messages.len(),
+                arg
+            ),
+            Err(e) => eprintln!("❌ {}", e),
+        },
+        other => eprintln!("❌ Unknown command '/{}'. Type /help for commands.", other),
+    }
+
+    true
+}
+
+fn save_transcript(messages: &[Message], path: &Path) -> Result<()> {
+    let content = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::to_string_pretty(messages).context("Failed to serialize conversation")?
+    } else {
+        messages
+            .iter()
+            .map(|message| format!("## {}\n\n{}\n", message.role, message.text()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    fs::write(path, content)
+        .with_context(|| format!("Failed to write conversation: {}", path.display()))
+}
@@ -0,0 +1,496 @@
+use super::{Config, apply_profile, get_config_path, get_model_config};
+use crate::utils::proxy::parse_proxy_url;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Error and warning counts for `xllm config check`
+#[derive(Default)]
+struct Diagnostics {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Diagnostics {
+    fn error(&mut self, line: Option<usize>, message: impl Display) {
+        self.errors += 1;
+        println!("❌ {}{}", line_prefix(line), message);
+    }
+
+    fn warning(&mut self, line: Option<usize>, message: impl Display) {
+        self.warnings += 1;
+        println!("⚠️  {}{}", line_prefix(line), message);
+    }
+}
+
+fn line_prefix(line: Option<usize>) -> String {
+    line.map(|line| format!("line {}: ", line)).unwrap_or_default()
+}
+
+/// The line each table header and key is defined on, keyed by its path
+/// (`["models", "claude", "url"]`). Keys inside inline tables are not indexed.
+struct KeyLines {
+    lines: HashMap<Vec<String>, usize>,
+}
+
+impl KeyLines {
+    fn new(text: &str) -> Self {
+        let mut lines = HashMap::new();
+        let mut table: Vec<String> = Vec::new();
+        let mut in_multiline_string = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            // Lines of a multi-line string are values, not keys
+            let toggles_string = line.matches("\"\"\"").count() % 2 == 1
+                || line.matches("'''").count() % 2 == 1;
+            if in_multiline_string {
+                in_multiline_string = !toggles_string;
+                continue;
+            }
+            in_multiline_string = toggles_string;
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = trimmed.strip_prefix('[') {
+                let header = header.trim_start_matches('[');
+                let header = header.split(']').next().unwrap_or_default();
+                table = split_key(header);
+                lines.entry(table.clone()).or_insert(line_number);
+            } else if let Some((key, _)) = trimmed.split_once('=') {
+                let mut path = table.clone();
+                path.extend(split_key(key));
+                lines.entry(path).or_insert(line_number);
+            }
+        }
+        KeyLines { lines }
+    }
+
+    /// Line of `path`, else of the first key below it, else of its closest parent
+    fn find(&self, path: &[&str]) -> Option<usize> {
+        let path: Vec<String> = path.iter().map(|segment| segment.to_string()).collect();
+        for len in (1..=path.len()).rev() {
+            let prefix = &path[..len];
+            if let Some(&line) = self.lines.get(prefix) {
+                return Some(line);
+            }
+            let first_below = self
+                .lines
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(_, &line)| line)
+                .min();
+            if first_below.is_some() {
+                return first_below;
+            }
+        }
+        None
+    }
+
+    /// Line of a setting in the effective config: where the selected profile
+    /// sets it, otherwise where the file sets it
+    fn find_effective(&self, profile: Option<&str>, path: &[&str]) -> Option<usize> {
+        if let Some(profile) = profile {
+            let mut in_profile = vec!["profiles", profile];
+            in_profile.extend_from_slice(path);
+            let in_profile: Vec<String> = in_profile.iter().map(|s| s.to_string()).collect();
+            if let Some(&line) = self.lines.get(&in_profile) {
+                return Some(line);
+            }
+        }
+        self.find(path)
+    }
+}
+
+/// Split a dotted TOML key into segments, removing quotes
+fn split_key(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in key.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '.') => segments.push(std::mem::take(&mut current).trim().to_string()),
+            (_, c) => current.push(c),
+        }
+    }
+    segments.push(current.trim().to_string());
+    segments
+}
+
+/// Line number of a byte offset
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Validate the config file and report problems with the line they are on.
+/// `config` is the config loaded at startup, with the selected profile applied.
+pub async fn check_config(
+    config: Result<&Config, &anyhow::Error>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let path = get_config_path()?;
+    let text = fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+    let path = std::path::absolute(&path).unwrap_or(path);
+    println!("🔍 Checking {}", path.display());
+
+    let mut diagnostics = Diagnostics::default();
+
+    // The remaining checks need a file that parses
+    if check_file(&text, &mut diagnostics) {
+        match config {
+            Ok(config) => {
+                let lines = KeyLines::new(&text);
+                check_env_variables(&text, &mut diagnostics);
+                check_models(config, &lines, profile, &mut diagnostics);
+                check_proxy(config, &lines, profile, &mut diagnostics).await;
+            }
+            Err(e) => diagnostics.error(None, format!("{:#}", e)),
+        }
+    }
+
+    if diagnostics.errors > 0 {
+        return Err(anyhow::anyhow!(
+            "{} has {} and {}",
+            path.display(),
+            plural(diagnostics.errors, "error"),
+            plural(diagnostics.warnings, "warning")
+        ));
+    }
+    if diagnostics.warnings > 0 {
+        println!("⚠️  Config is usable with {}", plural(diagnostics.warnings, "warning"));
+    } else {
+        println!("✅ Config OK");
+    }
+    Ok(())
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// TOML syntax, the config schema, unknown keys and every profile. Returns
+/// whether the file itself parses.
+fn check_file(text: &str, diagnostics: &mut Diagnostics) -> bool {
+    let table: toml::Table = match toml::from_str(text) {
+        Ok(table) => table,
+        Err(e) => {
+            let line = e.span().map(|span| line_at(text, span.start));
+            diagnostics.error(line, format!("Invalid TOML: {}", e.message()));
+            return false;
+        }
+    };
+    let config: Config = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            let line = e.span().map(|span| line_at(text, span.start));
+            diagnostics.error(line, e.message());
+            return false;
+        }
+    };
+
+    let lines = KeyLines::new(text);
+    if let Ok(toml::Value::Table(known)) = toml::Value::try_from(&config) {
+        check_unknown_keys(&table, &known, &[], &lines, diagnostics);
+    }
+
+    let profiles = table
+        .get("profiles")
+        .and_then(|profiles| profiles.as_table())
+        .cloned()
+        .unwrap_or_default();
+    for (name, overlay) in &profiles {
+        let line = lines.find(&["profiles", name]);
+        let mut merged = table.clone();
+        if let Err(e) = apply_profile(&mut merged, name) {
+            diagnostics.error(line, e);
+            continue;
+        }
+        let config: Config = match toml::Value::Table(merged).try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                diagnostics.error(line, format!("Profile {}: {}", name, e.message()));
+                continue;
+            }
+        };
+        if let (Some(overlay), Ok(toml::Value::Table(known))) =
+            (overlay.as_table(), toml::Value::try_from(&config))
+        {
+            check_unknown_keys(overlay, &known, &["profiles", name], &lines, diagnostics);
+        }
+    }
+    true
+}
+
+/// Warn about keys that are not part of the config and would be ignored,
+/// found by comparing the file against the config it deserializes to
+fn check_unknown_keys(
+    table: &toml::Table,
+    known: &toml::Table,
+    path: &[&str],
+    lines: &KeyLines,
+    diagnostics: &mut Diagnostics,
+) {
+    for (key, value) in table {
+        if path.is_empty() && key == "profiles" {
+            continue;
+        }
+        let mut key_path = path.to_vec();
+        key_path.push(key);
+        match known.get(key) {
+            None => diagnostics.warning(
+                lines.find(&key_path),
+                format!("Unknown key {} is ignored", key_path.join(".")),
+            ),
+            Some(toml::Value::Table(known)) => {
+                if let toml::Value::Table(table) = value {
+                    check_unknown_keys(table, known, &key_path, lines, diagnostics);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// `${VAR}` placeholders whose variable is not set
+fn check_env_variables(text: &str, diagnostics: &mut Diagnostics) {
+    let placeholder = regex::Regex::new(r"\$\{([^}]+)\}").unwrap();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for caps in placeholder.captures_iter(line) {
+            if std::env::var(&caps[1]).is_err() {
+                diagnostics.warning(
+                    Some(index + 1),
+                    format!("${{{}}} is not set, so it resolves to an empty value", &caps[1]),
+                );
+            }
+        }
+    }
+}
+
+/// URLs and API keys of each configured model, the default model and aliases
+fn check_models(
+    config: &Config,
+    lines: &KeyLines,
+    profile: Option<&str>,
+    diagnostics: &mut Diagnostics,
+) {
+    let models = &config.models;
+    if models.claude.is_none()
+        && models.openai.is_none()
+        && models.ollama.is_none()
+        && models.custom.is_empty()
+    {
+        diagnostics.error(lines.find(&["models"]), "No models are configured");
+    }
+
+    let mut check_url = |path: &[&str], url: &str| {
+        if let Err(message) = validate_url(url) {
+            diagnostics.error(
+                lines.find_effective(profile, path),
+                format!("{} {}: {}", path.join("."), url, message),
+            );
+        }
+    };
+    if let Some(claude) = &models.claude {
+        check_url(&["models", "claude", "url"], &claude.url);
+    }
+    if let Some(openai) = &models.openai {
+        check_url(&["models", "openai", "url"], &openai.url);
+    }
+    if let Some(ollama) = &models.ollama {
+        check_url(&["models", "ollama", "url"], &ollama.url);
+    }
+    for (name, custom) in &models.custom {
+        check_url(&["models", "custom", name, "url"], &custom.url);
+    }
+
+    if let Some(claude) = &models.claude
+        && claude.anthropic_api_key.is_empty()
+    {
+        let path = ["models", "claude", "anthropic_api_key"];
+        diagnostics.error(
+            lines.find_effective(profile, &path),
+            "models.claude.anthropic_api_key is empty",
+        );
+    }
+    if let Some(openai) = &models.openai
+        && openai.api_key.is_empty()
+    {
+        let path = ["models", "openai", "api_key"];
+        diagnostics.error(
+            lines.find_effective(profile, &path),
+            "models.openai.api_key is empty",
+        );
+    }
+    for (name, custom) in &models.custom {
+        if custom.api_key.as_deref() == Some("") {
+            let path = ["models", "custom", name, "api_key"];
+            diagnostics.warning(
+                lines.find_effective(profile, &path),
+                format!("models.custom.{}.api_key is empty", name),
+            );
+        }
+    }
+
+    // Only the default model's credentials are resolved, as a prompt would
+    if let Err(e) = get_model_config(config, None)
+        .and_then(|mut provider| provider.resolve_credentials())
+    {
+        diagnostics.error(
+            lines.find_effective(profile, &["global", "default_model"]),
+            format!("Default model: {:#}", e),
+        );
+    }
+    for name in config.aliases.keys() {
+        if let Err(e) = get_model_config(config, Some(name)) {
+            diagnostics.error(
+                lines.find_effective(profile, &["aliases", name]),
+                format!("{:#}", e),
+            );
+        }
+    }
+}
+
+/// An absolute http(s) URL with a host
+fn validate_url(url: &str) -> std::result::Result<(), String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("not a valid URL ({})", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("expected http or https, not {}", parsed.scheme()));
+    }
+    if parsed.host_str().is_none() {
+        return Err("no host".to_string());
+    }
+    Ok(())
+}
+
+/// The proxy URL, and whether the proxy accepts connections when it is enabled
+async fn check_proxy(
+    config: &Config,
+    lines: &KeyLines,
+    profile: Option<&str>,
+    diagnostics: &mut Diagnostics,
+) {
+    let Some(global) = &config.global else {
+        return;
+    };
+    let line = lines.find_effective(profile, &["global", "proxy_url"]);
+    if global.proxy_url.is_empty() {
+        if global.proxy {
+            diagnostics.error(line, "Proxy is enabled but global.proxy_url is empty");
+        }
+        return;
+    }
+    let address = match parse_proxy_url(&global.proxy_url) {
+        Ok(address) => address,
+        Err(e) => {
+            diagnostics.error(line, format!("global.proxy_url {}: {:#}", global.proxy_url, e));
+            return;
+        }
+    };
+    if !global.proxy {
+        return;
+    }
+
+    let timeout = Duration::from_secs(global.connect_timeout_secs);
+    match tokio::time::timeout(timeout, TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => println!("✅ Proxy {} is reachable", address),
+        Ok(Err(e)) => diagnostics.error(line, format!("Cannot reach proxy {}: {}", address, e)),
+        Err(_) => diagnostics.error(
+            line,
+            format!(
+                "Cannot reach proxy {}: timed out after {}s",
+                address,
+                timeout.as_secs()
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"# xllm config
+[global]
+proxy = false
+
+[models.claude]
+model = "claude-sonnet-4-20250514"
+system = """
+url = "not a key"
+"""
+url = "https://api.anthropic.com/"
+
+[models.custom."my.server"]
+url = "http://localhost:8000/"
+
+[[tools.list]]
+name = "x"
+
+[profiles.work.models.claude]
+url = "https://proxy.example/"
+"#;
+
+    #[test]
+    fn finds_headers_and_keys() {
+        let lines = KeyLines::new(TEXT);
+        assert_eq!(lines.find(&["global"]), Some(2));
+        assert_eq!(lines.find(&["global", "proxy"]), Some(3));
+        assert_eq!(lines.find(&["models", "claude", "model"]), Some(6));
+        assert_eq!(lines.find(&["tools", "list", "name"]), Some(16));
+    }
+
+    #[test]
+    fn skips_multiline_strings() {
+        let lines = KeyLines::new(TEXT);
+        assert_eq!(lines.find(&["models", "claude", "url"]), Some(10));
+    }
+
+    #[test]
+    fn handles_quoted_keys() {
+        let lines = KeyLines::new(TEXT);
+        assert_eq!(
+            lines.find(&["models", "custom", "my.server", "url"]),
+            Some(13)
+        );
+        assert_eq!(
+            split_key(r#"models.custom."my.server".'x y'"#),
+            ["models", "custom", "my.server", "x y"]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_first_key_below_then_parent() {
+        let lines = KeyLines::new(TEXT);
+        // [models] has no header of its own
+        assert_eq!(lines.find(&["models"]), Some(5));
+        assert_eq!(lines.find(&["global", "default_model"]), Some(2));
+        assert_eq!(lines.find(&["cache"]), None);
+    }
+
+    #[test]
+    fn prefers_the_selected_profile() {
+        let lines = KeyLines::new(TEXT);
+        let url = ["models", "claude", "url"];
+        assert_eq!(lines.find_effective(Some("work"), &url), Some(19));
+        assert_eq!(lines.find_effective(Some("home"), &url), Some(10));
+        assert_eq!(lines.find_effective(None, &url), Some(10));
+    }
+
+    #[test]
+    fn line_at_counts_from_one() {
+        assert_eq!(line_at("a\nb\nc", 0), 1);
+        assert_eq!(line_at("a\nb\nc", 2), 2);
+        assert_eq!(line_at("a\nb\nc", 100), 3);
+    }
+}
@@ -1,19 +1,65 @@
+pub mod check;
+
+use crate::auth::{is_secret_reference, resolve_secret};
+use crate::cache::{DEFAULT_CACHE_MAX_BYTES, DEFAULT_CACHE_TTL_SECS};
+use crate::models::Usage;
+use crate::models::claude::ToolDefinition;
+use crate::transport::{DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_TIMEOUT_SECS};
+use crate::utils::context::DEFAULT_CONTEXT_MAX_BYTES;
+use crate::utils::retry::{
+    DEFAULT_MAX_RETRIES, DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_MAX_DELAY_MS,
+};
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
 pub fn resolve_env_variables(input: &str) -> String {
-    let re = regex::Regex::new(r"\$\{([^}]+)\}").unwrap();
-    re.replace_all(input, |caps: &regex::Captures| {
-        std::env::var(&caps[1]).unwrap_or_else(|_| "".to_string())
-    })
-    .to_string()
+    substitute_placeholders(input, r"\$\{([^}]+)\}", |name| std::env::var(name).ok()).0
 }
 
-pub fn get_config_path() -> Result<PathBuf> {
-    // Try multiple locations in order of preference
-    let possible_paths = vec![
+/// Fill `{{var}}` placeholders in a template. Every placeholder must have a value.
+pub fn resolve_template_variables(input: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let (output, missing) = substitute_placeholders(input, r"\{\{\s*([\w.-]+)\s*\}\}", |name| {
+        vars.get(name).cloned()
+    });
+
+    if missing.is_empty() {
+        Ok(output)
+    } else {
+        Err(anyhow::anyhow!(
+            "Missing template variables: {} (pass them with --var NAME=VALUE)",
+            missing.join(", ")
+        ))
+    }
+}
+
+/// Replace each match of `pattern` with the value `lookup` returns for its first
+/// capture group. Placeholders without a value become empty and their names are returned.
+fn substitute_placeholders(
+    input: &str,
+    pattern: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let re = regex::Regex::new(pattern).unwrap();
+    let mut missing = Vec::new();
+    let output = re
+        .replace_all(input, |caps: &regex::Captures| {
+            lookup(&caps[1]).unwrap_or_else(|| {
+                if !missing.iter().any(|name| name == &caps[1]) {
+                    missing.push(caps[1].to_string());
+                }
+                "".to_string()
+            })
+        })
+        .to_string();
+    (output, missing)
+}
+
+/// Where the config file is looked for, in order of preference
+pub fn config_path_candidates() -> Vec<PathBuf> {
+    vec![
         // 1. Current directory (for development)
         PathBuf::from("config.toml"),
         // 2. XDG config directory (~/.config/xllm/config.toml)
@@ -24,12 +70,12 @@ pub fn get_config_path() -> Result<PathBuf> {
         dirs::home_dir()
             .map(|p| p.join(".xllm.toml"))
             .unwrap_or_else(|| PathBuf::from("~/.xllm.toml")),
-    ];
+    ]
+}
 
-    for path in possible_paths {
-        if path.exists() {
-            return Ok(path);
-        }
+pub fn get_config_path() -> Result<PathBuf> {
+    if let Some(path) = config_path_candidates().into_iter().find(|path| path.exists()) {
+        return Ok(path);
     }
 
     // If no config found, create the default config directory and provide helpful error
@@ -43,6 +89,97 @@ pub fn get_config_path() -> Result<PathBuf> {
     ))
 }
 
+/// Print each place the config file is looked for, marking the one in use
+pub fn print_config_paths() -> Result<()> {
+    let in_use = get_config_path().ok();
+    for path in config_path_candidates() {
+        let shown = std::path::absolute(&path).unwrap_or_else(|_| path.clone());
+        if Some(&path) == in_use.as_ref() {
+            println!("→ {} (in use)", shown.display());
+        } else if path.exists() {
+            println!("  {} (exists, not used)", shown.display());
+        } else {
+            println!("  {} (not found)", shown.display());
+        }
+    }
+    if in_use.is_none() {
+        println!("\nNo config file found. Create one with `xllm --init`.");
+    }
+    Ok(())
+}
+
+/// Print the effective config, with the profile applied, defaults filled in
+/// and secrets masked. Credential references are shown as written.
+pub fn show_config(config: &Config, profile: Option<&str>) -> Result<()> {
+    let mut value = toml::Value::try_from(config).context("Failed to serialize config")?;
+    mask_secrets(&mut value);
+
+    let path = get_config_path()?;
+    println!("# {}", std::path::absolute(&path).unwrap_or(path).display());
+    if let Some(profile) = profile {
+        println!("# profile: {}", profile);
+    }
+    print!(
+        "{}",
+        toml::to_string_pretty(&value).context("Failed to serialize config")?
+    );
+    Ok(())
+}
+
+/// Mask string values whose key names a credential, e.g. `api_key` or `Authorization`
+fn mask_secrets(value: &mut toml::Value) {
+    let toml::Value::Table(table) = value else {
+        return;
+    };
+    for (key, value) in table.iter_mut() {
+        let secret = key
+            .to_lowercase()
+            .split(['_', '-'])
+            .any(|word| ["key", "token", "secret", "password", "authorization"].contains(&word));
+        match value {
+            toml::Value::String(text) if secret && !is_secret_reference(text) => {
+                *text = mask_secret(text)
+            }
+            toml::Value::Table(_) => mask_secrets(value),
+            _ => {}
+        }
+    }
+}
+
+/// Keep just enough of a secret to tell keys apart
+fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len().min(8));
+    }
+    let start: String = chars[..4].iter().collect();
+    let end: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", start, end)
+}
+
+/// Open the config file in `$VISUAL` or `$EDITOR` (vi if neither is set)
+pub fn edit_config() -> Result<()> {
+    let path = get_config_path()?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    // Allow editors with arguments, such as `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor: {}", editor))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("Editor {} exited with {}", editor, status));
+    }
+    Ok(())
+}
+
 pub fn create_default_config() -> Result<()> {
     let config_dir = dirs::config_dir()
         .map(|p| p.join("xllm"))
@@ -68,19 +205,120 @@ pub fn create_default_config() -> Result<()> {
     let default_config = r#"[global]
 proxy = false
 proxy_url = "https://localhost:50051"
+# Model used when -m is not given (an alias, model ID or provider name)
+# default_model = "sonnet4"
+# Limits on the total --file context; files past the budget are dropped
+# context_max_bytes = 200000
+# context_max_tokens = 50000
+# Retries with exponential backoff on 429/5xx/529 and connection errors
+# max_retries = 3
+# retry_base_delay_ms = 1000
+# retry_max_delay_ms = 30000
+# Connect and whole-request timeouts; models can override both
+# connect_timeout_secs = 10
+# timeout_secs = 600
 
 [models.claude]
 model = "claude-sonnet-4-20250514"
 max_tokens = 1024
 anthropic_api_key = "${ANTHROPIC_API_KEY}"
+# Or keep the key out of this file with "keyring:anthropic" (saved with
+# `xllm auth set anthropic`) or a credential helper: "cmd:pass show anthropic"
 url = "https://api.anthropic.com/"
+# Default system prompt; --system and --system-file take precedence
+# system = "Answer tersely. Assume Rust 2024 edition."
+# timeout_secs = 900
 
-# Future models can be added here, e.g.:
+# Short names for -m, in addition to the built-in opus4, sonnet4, sonnet3 and
+# haiku3. A full model ID such as -m claude-opus-4-1-20250805 also works.
+# [aliases]
+# opus41 = { provider = "claude", model = "claude-opus-4-1-20250805" }
+# fast = { provider = "ollama", model = "llama3.2:1b" }
+
+# Profiles override any of the settings above. Select one with --profile or
+# XLLM_PROFILE, e.g. `xllm --profile work "..."`.
+# [profiles.work.global]
+# proxy = true
+# default_model = "opus4"
+# [profiles.work.models.claude]
+# anthropic_api_key = "${WORK_ANTHROPIC_API_KEY}"
+#
+# [profiles.home.global]
+# proxy = false
+
+# Uncomment to enable OpenAI models (-m openai, -m gpt-4o, ...)
 # [models.openai]
-# model = "gpt-4"
+# model = "gpt-4o"
 # max_tokens = 1024
 # api_key = "${OPENAI_API_KEY}"
 # url = "https://api.openai.com/"
+
+# Uncomment to use a local Ollama server (-m ollama, -m ollama:<model>)
+# [models.ollama]
+# model = "llama3.2"
+# url = "http://localhost:11434"
+
+# Any number of OpenAI-compatible servers (vLLM, llama.cpp, LM Studio, LiteLLM, ...)
+# can be registered and selected by name (-m local, -m local:<model>)
+# [models.custom.local]
+# url = "http://localhost:8000"
+# model = "meta-llama/Llama-3.1-8B-Instruct"
+# api_key = "${LOCAL_API_KEY}"
+# max_tokens = 1024
+# headers = { "X-Team" = "platform" }
+
+# Prompt templates with {{var}} placeholders (xllm -T review --var lang=rust --file x.rs).
+# Templates can also live in ~/.config/xllm/templates/<name>.toml
+# [templates.review]
+# prompt = "Review this {{lang}} code for bugs and unidiomatic patterns."
+# system = "You are a senior {{lang}} reviewer. Be concise."
+# model = "sonnet4"
+# max_tokens = 2048
+
+# Local commands Claude can call when run with --tools. Each run asks for
+# confirmation unless confirm = false.
+# [tools.git_log]
+# description = "Show recent commits in the current repository"
+# command = ["git", "log", "--oneline", "-n", "{{count}}"]
+# input_schema = { type = "object", properties = { count = { type = "integer" } }, required = ["count"] }
+
+# Prices in USD per million tokens, used by --usage and `xllm usage`. Keys are
+# model IDs as reported by the API; a key also covers longer IDs it prefixes.
+[prices.claude-opus-4]
+input = 15.0
+output = 75.0
+cache_write = 18.75
+cache_read = 1.50
+
+[prices.claude-sonnet-4]
+input = 3.0
+output = 15.0
+cache_write = 3.75
+cache_read = 0.30
+
+[prices.claude-3-7-sonnet]
+input = 3.0
+output = 15.0
+cache_write = 3.75
+cache_read = 0.30
+
+[prices.claude-3-5-haiku]
+input = 0.80
+output = 4.0
+cache_write = 1.0
+cache_read = 0.08
+
+# [prices.gpt-4o]
+# input = 2.50
+# output = 10.0
+# cache_read = 1.25
+
+# Reuse responses to identical requests (same model, messages and parameters).
+# Bypass with --no-cache, or --refresh to replace the cached response.
+# [cache]
+# enabled = true
+# ttl_secs = 86400
+# max_bytes = 104857600
 "#;
 
     fs::write(&config_path, default_config)
@@ -91,84 +329,895 @@ url = "https://api.anthropic.com/"
 
     Ok(())
 }
-pub fn load_config() -> Result<Config> {
+
+/// Templates directory (~/.config/xllm/templates)
+pub fn get_templates_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|p| p.join("xllm").join("templates"))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))
+}
+
+/// Load the config file, with the settings of `profile` (from `[profiles.<name>]`)
+/// laid over the rest of the file. Credentials are left as written and only
+/// resolved for the provider a command uses, by `ModelProvider::resolve_credentials`.
+pub fn load_config(profile: Option<&str>) -> Result<Config> {
     let config_path = get_config_path()?;
 
     let config_content = fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
 
-    let mut config: Config = toml::from_str(&config_content)
+    let mut table: toml::Table = toml::from_str(&config_content)
         .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+    if let Some(profile) = profile {
+        apply_profile(&mut table, profile)?;
+    }
+
+    toml::Value::Table(table)
+        .try_into()
+        .with_context(|| format!("Failed to parse config file: {}", config_path.display()))
+}
 
-    // Resolve environment variables for all models
-    if let Some(claude_config) = &mut config.models.claude {
-        claude_config.anthropic_api_key = resolve_env_variables(&claude_config.anthropic_api_key);
+/// The `-m` help text, listing the aliases of the config if it loads
+pub fn model_help(profile: Option<&str>) -> String {
+    format!(
+        "Model to use: {}",
+        supported_models(load_config(profile).ok().as_ref())
+    )
+}
+
+/// Merge `[profiles.<name>]` into the top level of the config. Tables are
+/// merged key by key, so a profile only needs the settings it changes.
+fn apply_profile(table: &mut toml::Table, name: &str) -> Result<()> {
+    let profiles = table.get("profiles").and_then(|profiles| profiles.as_table());
+    let Some(profile) = profiles.and_then(|profiles| profiles.get(name)) else {
+        let available: Vec<&str> = profiles
+            .map(|profiles| profiles.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        return Err(anyhow::anyhow!(
+            "Unknown profile: {}. Available profiles: {}",
+            name,
+            if available.is_empty() { "none".to_string() } else { available.join(", ") }
+        ));
+    };
+    let toml::Value::Table(profile) = profile.clone() else {
+        return Err(anyhow::anyhow!("[profiles.{}] must be a table", name));
+    };
+    if profile.contains_key("profiles") {
+        return Err(anyhow::anyhow!("[profiles.{}] cannot contain other profiles", name));
     }
 
-    Ok(config)
+    merge_tables(table, profile);
+    Ok(())
 }
 
-/// Get the appropriate model configuration based on model name
-pub fn get_model_config(config: &Config, model_name: &str) -> Result<ModelProvider> {
-    match model_name {
-        "opus4" | "sonnet4" | "sonnet3" | "haiku3" => {
-            if let Some(claude_config) = &config.models.claude {
-                Ok(ModelProvider::Claude(claude_config.clone()))
-            } else {
-                Err(anyhow::anyhow!(
-                    "Claude configuration not found for model: {}",
-                    model_name
-                ))
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
             }
         }
-        // Future models can be added here:
-        // "gpt-4" | "gpt-3.5" => {
-        //     if let Some(openai_config) = &config.models.openai {
-        //         Ok(ModelProvider::OpenAI(openai_config.clone()))
-        //     } else {
-        //         Err(anyhow::anyhow!("OpenAI configuration not found for model: {}", model_name))
-        //     }
-        // }
-        _ => Err(anyhow::anyhow!(
-            "Unknown model: {}. Supported models: opus4, sonnet4, sonnet3, haiku3",
+    }
+}
+
+/// Built-in `-m` aliases. Entries in `[aliases]` add to or replace these.
+const DEFAULT_ALIASES: &[(&str, &str, &str)] = &[
+    ("opus4", "claude", "claude-opus-4-20250514"),
+    ("sonnet4", "claude", "claude-sonnet-4-20250514"),
+    ("sonnet3", "claude", "claude-3-7-sonnet-latest"),
+    ("haiku3", "claude", "claude-3-5-haiku-latest"),
+];
+
+/// The alias table: built-in defaults overlaid with `[aliases]`
+pub fn model_aliases(config: Option<&Config>) -> BTreeMap<String, AliasConfig> {
+    let mut aliases: BTreeMap<String, AliasConfig> = DEFAULT_ALIASES
+        .iter()
+        .map(|(name, provider, model)| {
+            (
+                name.to_string(),
+                AliasConfig {
+                    provider: provider.to_string(),
+                    model: model.to_string(),
+                },
+            )
+        })
+        .collect();
+    if let Some(config) = config {
+        aliases.extend(config.aliases.clone());
+    }
+    aliases
+}
+
+/// Aliases served by the same endpoint as `target`, with the model ID each
+/// stands for. Aliases that fail to resolve are left out.
+pub fn aliases_for(config: &Config, target: &ModelProvider) -> Vec<(String, String)> {
+    model_aliases(Some(config))
+        .into_keys()
+        .filter_map(|name| {
+            let resolved = get_model_config(config, Some(&name)).ok()?;
+            resolved
+                .same_endpoint(target)
+                .then(|| (name, resolved.model_id().to_string()))
+        })
+        .collect()
+}
+
+/// Every accepted form of `-m`, for help text and error messages
+pub fn supported_models(config: Option<&Config>) -> String {
+    let aliases: Vec<String> = model_aliases(config).into_keys().collect();
+    let custom: String = config
+        .map(|config| {
+            config
+                .models
+                .custom
+                .keys()
+                .map(|name| format!(", {}, {}:<model>", name, name))
+                .collect()
+        })
+        .unwrap_or_default();
+    format!(
+        "{}, a full model ID (claude-*, gpt-*, o1/o3/o4), claude:<model>, openai, openai:<model>, ollama, ollama:<model>{}",
+        aliases.join(", "),
+        custom
+    )
+}
+
+/// Get the appropriate model configuration based on model name.
+/// The returned config has its `model` set to the concrete model ID to request;
+/// without a model name the configured Claude default is used.
+pub fn get_model_config(config: &Config, model_name: Option<&str>) -> Result<ModelProvider> {
+    let default_model = config
+        .global
+        .as_ref()
+        .and_then(|global| global.default_model.as_deref());
+    let Some(model_name) = model_name.or(default_model) else {
+        return claude_config(config, "claude").map(ModelProvider::Claude);
+    };
+
+    match model_aliases(Some(config)).get(model_name) {
+        Some(alias) => {
+            let known = ["claude", "openai", "ollama"].contains(&alias.provider.as_str())
+                || config.models.custom.contains_key(&alias.provider);
+            if !known {
+                return Err(anyhow::anyhow!(
+                    "Alias {} uses unknown provider {}. Use claude, openai, ollama or a [models.custom.<name>] endpoint",
+                    model_name,
+                    alias.provider
+                ));
+            }
+            let target = format!("{}:{}", alias.provider, alias.model);
+            resolve_model(config, &target)
+                .with_context(|| format!("Failed to resolve alias {} ({})", model_name, target))
+        }
+        None => resolve_model(config, model_name),
+    }
+}
+
+/// Resolve a model name that is not an alias: a provider with an optional
+/// `:<model>`, a custom endpoint, or a full model ID
+fn resolve_model(config: &Config, model_name: &str) -> Result<ModelProvider> {
+    // "claude" uses the configured default; "claude:<model>" and full
+    // Claude model IDs pick one
+    if model_name == "claude" {
+        return claude_config(config, model_name).map(ModelProvider::Claude);
+    }
+    let claude_model = match model_name.split_once(':') {
+        Some(("claude", model)) => Some(model),
+        _ if model_name.starts_with("claude-") => Some(model_name),
+        _ => None,
+    };
+    if let Some(model) = claude_model {
+        let mut claude_config = claude_config(config, model_name)?;
+        claude_config.model = model.to_string();
+        return Ok(ModelProvider::Claude(claude_config));
+    }
+
+    // Named OpenAI-compatible endpoints: "<name>" or "<name>:<model>"
+    let (endpoint, endpoint_model) = match model_name.split_once(':') {
+        Some((endpoint, model)) => (endpoint, Some(model)),
+        None => (model_name, None),
+    };
+    if let Some(custom_config) = config.models.custom.get(endpoint) {
+        let mut openai_config = custom_config.to_openai_config();
+        if let Some(model) = endpoint_model {
+            openai_config.model = model.to_string();
+        }
+        return Ok(ModelProvider::OpenAI(openai_config));
+    }
+
+    // "ollama" uses the configured default, "ollama:<model>" picks a local model
+    if model_name == "ollama" || model_name.starts_with("ollama:") {
+        let mut ollama_config = config.models.ollama.clone().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Ollama configuration not found for model: {}. Add a [models.ollama] section to config.toml",
+                model_name
+            )
+        })?;
+        if let Some(model) = model_name.strip_prefix("ollama:") {
+            ollama_config.model = model.to_string();
+        }
+        return Ok(ModelProvider::Ollama(ollama_config));
+    }
+
+    // "openai" uses the configured default, "openai:<model>" and GPT/o-series names pick one
+    let openai_model = match model_name.split_once(':') {
+        Some(("openai", model)) => Some(model),
+        _ if model_name == "openai" => None,
+        _ if is_openai_model(model_name) => Some(model_name),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown model: {}. Supported models: {}",
+                model_name,
+                supported_models(Some(config))
+            ));
+        }
+    };
+
+    let mut openai_config = config.models.openai.clone().ok_or_else(|| {
+        anyhow::anyhow!(
+            "OpenAI configuration not found for model: {}. Add a [models.openai] section to config.toml",
             model_name
-        )),
+        )
+    })?;
+    if let Some(model) = openai_model {
+        openai_config.model = model.to_string();
     }
+    Ok(ModelProvider::OpenAI(openai_config))
+}
+
+fn claude_config(config: &Config, model_name: &str) -> Result<ClaudeConfig> {
+    config.models.claude.clone().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Claude configuration not found for model: {}",
+            model_name
+        )
+    })
+}
+
+fn is_openai_model(model_name: &str) -> bool {
+    model_name.starts_with("gpt-")
+        || ["o1", "o3", "o4"]
+            .iter()
+            .any(|prefix| model_name == *prefix || model_name.starts_with(&format!("{}-", prefix)))
 }
 
 /// Enum to represent different model providers
 #[derive(Debug, Clone)]
 pub enum ModelProvider {
     Claude(ClaudeConfig),
-    // Future providers:
-    // OpenAI(OpenAIConfig),
+    OpenAI(OpenAIConfig),
+    Ollama(OllamaConfig),
+}
+
+impl ModelProvider {
+    /// Resolve the `${VAR}`, `keyring:` and `cmd:` credentials this provider
+    /// sends. Other providers' credentials are never resolved, so a broken
+    /// helper for an unused provider doesn't get in the way.
+    pub fn resolve_credentials(&mut self) -> Result<()> {
+        match self {
+            ModelProvider::Claude(config) => {
+                config.anthropic_api_key = resolve_secret(&config.anthropic_api_key)
+                    .context("Failed to resolve the Claude API key")?;
+            }
+            ModelProvider::OpenAI(config) => {
+                config.api_key = resolve_secret(&config.api_key)
+                    .with_context(|| format!("Failed to resolve the API key for {}", config.url))?;
+                for (header, value) in config.headers.iter_mut() {
+                    *value = resolve_secret(value).with_context(|| {
+                        format!("Failed to resolve the {} header for {}", header, config.url)
+                    })?;
+                }
+            }
+            ModelProvider::Ollama(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Offer tools to the model. Only Claude supports tool use.
+    pub fn set_tools(&mut self, tools: Vec<ToolDefinition>) -> Result<()> {
+        match self {
+            ModelProvider::Claude(config) => {
+                config.tools = tools;
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!("Tool use is only supported with Claude models")),
+        }
+    }
+
+    /// Replace the configured system prompt, e.g. with `--system`
+    pub fn override_system(&mut self, system: String) {
+        *self.system_mut() = Some(system);
+    }
+
+    /// Add instructions after the system prompt, e.g. for `--json-schema`
+    pub fn append_system(&mut self, text: &str) {
+        let system = self.system_mut();
+        *system = Some(match system.take() {
+            Some(existing) => format!("{}\n\n{}", existing, text),
+            None => text.to_string(),
+        });
+    }
+
+    /// Timeouts set on the model itself, which take precedence over `[global]`
+    pub fn timeouts(&self) -> &TimeoutOverrides {
+        match self {
+            ModelProvider::Claude(config) => &config.timeouts,
+            ModelProvider::OpenAI(config) => &config.timeouts,
+            ModelProvider::Ollama(config) => &config.timeouts,
+        }
+    }
+
+    /// Base URL requests are sent to
+    pub fn url(&self) -> &str {
+        match self {
+            ModelProvider::Claude(config) => &config.url,
+            ModelProvider::OpenAI(config) => &config.url,
+            ModelProvider::Ollama(config) => &config.url,
+        }
+    }
+
+    /// Whether both talk to the same API at the same URL
+    pub fn same_endpoint(&self, other: &ModelProvider) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other) && self.url() == other.url()
+    }
+
+    fn model_id(&self) -> &str {
+        match self {
+            ModelProvider::Claude(config) => &config.model,
+            ModelProvider::OpenAI(config) => &config.model,
+            ModelProvider::Ollama(config) => &config.model,
+        }
+    }
+
+    fn system_mut(&mut self) -> &mut Option<String> {
+        match self {
+            ModelProvider::Claude(config) => &mut config.system,
+            ModelProvider::OpenAI(config) => &mut config.system,
+            ModelProvider::Ollama(config) => &mut config.system,
+        }
+    }
 }
 
 // Generic Config struct that can hold configurations for multiple AI providers
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub global: Option<GlobalConfig>,
     pub models: ModelsConfig,
+    #[serde(default)]
+    pub templates: BTreeMap<String, TemplateConfig>,
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolConfig>,
+    /// Prices keyed by model ID, from `[prices."<model>"]`
+    #[serde(default)]
+    pub prices: BTreeMap<String, PriceConfig>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Short `-m` names, from `[aliases]`
+    #[serde(default)]
+    pub aliases: BTreeMap<String, AliasConfig>,
+}
+
+/// What an alias in `[aliases]` stands for. `provider` is claude, openai,
+/// ollama or the name of a `[models.custom.<name>]` endpoint.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AliasConfig {
+    pub provider: String,
+    pub model: String,
+}
+
+/// On-disk response cache, from `[cache]`. Off unless enabled.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long a cached response stays valid
+    #[serde(default = "default_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Oldest entries are removed once the cache grows past this size
+    #[serde(default = "default_cache_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: false,
+            ttl_secs: DEFAULT_CACHE_TTL_SECS,
+            max_bytes: DEFAULT_CACHE_MAX_BYTES,
+        }
+    }
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    DEFAULT_CACHE_TTL_SECS
+}
+
+fn default_cache_max_bytes() -> u64 {
+    DEFAULT_CACHE_MAX_BYTES
+}
+
+/// USD per million tokens. Cache prices default to the input price.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PriceConfig {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_write: Option<f64>,
+    #[serde(default)]
+    pub cache_read: Option<f64>,
 }
 
-#[derive(Debug, Deserialize)]
+impl PriceConfig {
+    /// Cost in USD of a request with the given usage
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let per_token = |tokens: u64, price: f64| tokens as f64 * price / 1_000_000.0;
+        per_token(usage.input_tokens, self.input)
+            + per_token(usage.output_tokens, self.output)
+            + per_token(
+                usage.cache_creation_input_tokens,
+                self.cache_write.unwrap_or(self.input),
+            )
+            + per_token(
+                usage.cache_read_input_tokens,
+                self.cache_read.unwrap_or(self.input),
+            )
+    }
+}
+
+/// A local command the model can call, from `[tools.<name>]`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ToolConfig {
+    pub description: String,
+    /// Program and arguments. `{{field}}` placeholders are filled from the tool
+    /// input, which is also passed as JSON on stdin.
+    pub command: Vec<String>,
+    /// JSON schema for the tool input
+    #[serde(default = "default_input_schema")]
+    pub input_schema: serde_json::Value,
+    /// Ask before running the command
+    #[serde(default = "default_confirm")]
+    pub confirm: bool,
+}
+
+fn default_input_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_confirm() -> bool {
+    true
+}
+
+/// A named prompt with `{{var}}` placeholders, from `[templates.<name>]`
+/// or `<name>.toml` in the templates directory
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TemplateConfig {
+    pub prompt: String,
+    /// Model used unless `-m` is given
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub system: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GlobalConfig {
+    #[serde(default)]
     pub proxy: bool,
+    #[serde(default)]
     pub proxy_url: String,
+    /// Model used when `-m` is not given; Claude when unset
+    #[serde(default)]
+    pub default_model: Option<String>,
+    /// Total size limit for `--file` context
+    #[serde(default = "default_context_max_bytes")]
+    pub context_max_bytes: usize,
+    /// Optional limit on the estimated tokens of `--file` context
+    #[serde(default)]
+    pub context_max_tokens: Option<usize>,
+    /// Retries for rate limits, overloads and connection errors
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// First backoff delay; doubles on each retry
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    /// Upper bound on a single backoff delay
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub retry_max_delay_ms: u64,
+    /// Time allowed to establish a connection
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Time allowed for a whole request, including a streamed response
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_context_max_bytes() -> usize {
+    DEFAULT_CONTEXT_MAX_BYTES
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
 }
 
-#[derive(Debug, Deserialize)]
+fn default_retry_base_delay_ms() -> u64 {
+    DEFAULT_RETRY_BASE_DELAY_MS
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    DEFAULT_RETRY_MAX_DELAY_MS
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// Per-model overrides of the `[global]` timeouts
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct TimeoutOverrides {
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ModelsConfig {
     pub claude: Option<ClaudeConfig>,
-    // Future models can be added here:
-    // pub openai: Option<OpenAIConfig>,
-    // pub anthropic: Option<AnthropicConfig>,
+    pub openai: Option<OpenAIConfig>,
+    pub ollama: Option<OllamaConfig>,
+    /// OpenAI-compatible endpoints keyed by the name used with `-m`
+    #[serde(default)]
+    pub custom: BTreeMap<String, CustomConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ClaudeConfig {
     pub model: String,
     pub max_tokens: u32,
     pub url: String,
     pub anthropic_api_key: String,
+    /// Default system prompt for this model
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(flatten)]
+    pub timeouts: TimeoutOverrides,
+    /// Tools offered to the model, filled from `[tools]` when `--tools` is given
+    #[serde(skip)]
+    pub tools: Vec<ToolDefinition>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OpenAIConfig {
+    pub model: String,
+    pub max_tokens: u32,
+    pub url: String,
+    pub api_key: String,
+    /// Extra headers sent with every request
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Send `max_tokens` instead of `max_completion_tokens`, for compatible servers
+    #[serde(skip)]
+    pub legacy_max_tokens: bool,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(flatten)]
+    pub timeouts: TimeoutOverrides,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OllamaConfig {
+    pub model: String,
+    pub url: String,
+    /// Maps to Ollama's `num_predict`; the server default applies when unset
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(flatten)]
+    pub timeouts: TimeoutOverrides,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CustomConfig {
+    pub url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    #[serde(default = "default_custom_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(flatten)]
+    pub timeouts: TimeoutOverrides,
+}
+
+fn default_custom_max_tokens() -> u32 {
+    1024
+}
+
+impl CustomConfig {
+    /// Custom endpoints speak the OpenAI dialect, so they reuse the OpenAI provider
+    pub fn to_openai_config(&self) -> OpenAIConfig {
+        OpenAIConfig {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            url: self.url.clone(),
+            api_key: self.api_key.clone().unwrap_or_default(),
+            headers: self.headers.clone(),
+            legacy_max_tokens: true,
+            system: self.system.clone(),
+            timeouts: self.timeouts.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[models.claude]
+model = "claude-sonnet-4-20250514"
+max_tokens = 1024
+url = "https://api.anthropic.com/"
+anthropic_api_key = "${ANTHROPIC_API_KEY}"
+
+[models.openai]
+model = "gpt-4o"
+max_tokens = 1024
+url = "https://api.openai.com/"
+api_key = "${OPENAI_API_KEY}"
+
+[models.ollama]
+model = "llama3"
+url = "http://localhost:11434/"
+
+[models.custom.local]
+url = "http://localhost:8000/"
+model = "qwen"
+
+[aliases]
+fast = { provider = "local", model = "qwen-small" }
+sonnet4 = { provider = "claude", model = "claude-sonnet-4-5" }
+broken = { provider = "nowhere", model = "x" }
+"#;
+
+    fn config() -> Config {
+        toml::from_str(CONFIG).unwrap()
+    }
+
+    fn resolve(config: &Config, name: Option<&str>) -> (&'static str, String) {
+        let provider = get_model_config(config, name).unwrap();
+        let kind = match &provider {
+            ModelProvider::Claude(_) => "claude",
+            ModelProvider::OpenAI(_) => "openai",
+            ModelProvider::Ollama(_) => "ollama",
+        };
+        (kind, provider.model_id().to_string())
+    }
+
+    #[test]
+    fn without_a_name_uses_the_claude_default_or_default_model() {
+        let mut config = config();
+        assert_eq!(
+            resolve(&config, None),
+            ("claude", "claude-sonnet-4-20250514".to_string())
+        );
+
+        config.global = Some(toml::from_str("default_model = \"fast\"").unwrap());
+        assert_eq!(resolve(&config, None), ("openai", "qwen-small".to_string()));
+    }
+
+    #[test]
+    fn aliases_resolve_with_config_entries_replacing_built_ins() {
+        let config = config();
+        assert_eq!(
+            resolve(&config, Some("opus4")),
+            ("claude", "claude-opus-4-20250514".to_string())
+        );
+        assert_eq!(
+            resolve(&config, Some("sonnet4")),
+            ("claude", "claude-sonnet-4-5".to_string())
+        );
+
+        let ModelProvider::OpenAI(local) = get_model_config(&config, Some("fast")).unwrap() else {
+            panic!("fast should use the custom endpoint");
+        };
+        assert_eq!(local.url, "http://localhost:8000/");
+        assert_eq!(local.model, "qwen-small");
+    }
+
+    #[test]
+    fn alias_with_unknown_provider_is_an_error() {
+        let error = get_model_config(&config(), Some("broken")).unwrap_err();
+        assert!(error.to_string().contains("unknown provider nowhere"));
+    }
+
+    #[test]
+    fn full_ids_and_provider_prefixes_pick_the_provider() {
+        let config = config();
+        let cases = [
+            ("claude", ("claude", "claude-sonnet-4-20250514")),
+            ("claude:claude-3-opus", ("claude", "claude-3-opus")),
+            (
+                "claude-3-5-haiku-latest",
+                ("claude", "claude-3-5-haiku-latest"),
+            ),
+            ("openai", ("openai", "gpt-4o")),
+            ("openai:gpt-4.1", ("openai", "gpt-4.1")),
+            ("gpt-4o-mini", ("openai", "gpt-4o-mini")),
+            ("o3", ("openai", "o3")),
+            ("o4-mini", ("openai", "o4-mini")),
+            ("ollama", ("ollama", "llama3")),
+            ("ollama:mistral", ("ollama", "mistral")),
+            ("local", ("openai", "qwen")),
+            ("local:qwen-large", ("openai", "qwen-large")),
+        ];
+        for (name, (kind, model)) in cases {
+            assert_eq!(
+                resolve(&config, Some(name)),
+                (kind, model.to_string()),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_models_and_missing_sections_are_errors() {
+        let mut config = config();
+        let error = get_model_config(&config, Some("o30")).unwrap_err();
+        assert!(error.to_string().starts_with("Unknown model: o30"));
+
+        config.models.openai = None;
+        assert!(get_model_config(&config, Some("gpt-4o")).is_err());
+    }
+
+    #[test]
+    fn profile_merges_tables_key_by_key() {
+        let mut table: toml::Table = toml::from_str(
+            r#"
+[global]
+proxy = false
+default_model = "sonnet4"
+
+[models.claude]
+model = "claude-sonnet-4-20250514"
+anthropic_api_key = "${ANTHROPIC_API_KEY}"
+
+[profiles.work.global]
+proxy = true
+
+[profiles.work.models.claude]
+anthropic_api_key = "${WORK_KEY}"
+
+[profiles.work.aliases]
+fast = { provider = "claude", model = "claude-3-5-haiku-latest" }
+"#,
+        )
+        .unwrap();
+        apply_profile(&mut table, "work").unwrap();
+
+        let global = table["global"].as_table().unwrap();
+        assert_eq!(global["proxy"].as_bool(), Some(true));
+        assert_eq!(global["default_model"].as_str(), Some("sonnet4"));
+        let claude = table["models"]["claude"].as_table().unwrap();
+        assert_eq!(claude["model"].as_str(), Some("claude-sonnet-4-20250514"));
+        assert_eq!(claude["anthropic_api_key"].as_str(), Some("${WORK_KEY}"));
+        assert_eq!(
+            table["aliases"]["fast"]["model"].as_str(),
+            Some("claude-3-5-haiku-latest")
+        );
+    }
+
+    #[test]
+    fn merge_replaces_values_that_are_not_both_tables() {
+        let mut base: toml::Table = toml::from_str("a = { b = 1 }\nc = [1, 2]").unwrap();
+        let overlay: toml::Table = toml::from_str("a = 2\nc = [3]").unwrap();
+        merge_tables(&mut base, overlay);
+        assert_eq!(base["a"].as_integer(), Some(2));
+        assert_eq!(base["c"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_or_malformed_profiles_are_errors() {
+        let mut table: toml::Table =
+            toml::from_str("[profiles.work]\nx = 1\n[profiles.home]\ny = 2").unwrap();
+        let error = apply_profile(&mut table, "play").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown profile: play. Available profiles: work, home"
+        );
+
+        let mut table: toml::Table = toml::from_str("profiles = { odd = 1 }").unwrap();
+        assert!(apply_profile(&mut table, "odd").is_err());
+
+        let mut table: toml::Table = toml::from_str("[profiles.a.profiles.b]\nx = 1").unwrap();
+        assert!(apply_profile(&mut table, "a").is_err());
+
+        let mut table = toml::Table::new();
+        let error = apply_profile(&mut table, "work").unwrap_err();
+        assert!(error.to_string().ends_with("Available profiles: none"));
+    }
+
+    #[test]
+    fn substitute_placeholders_reports_each_missing_name_once() {
+        let (output, missing) =
+            substitute_placeholders("${A}-${B}-${A}-${C}", r"\$\{([^}]+)\}", |name| {
+                (name == "B").then(|| "b".to_string())
+            });
+        assert_eq!(output, "-b--");
+        assert_eq!(missing, ["A", "C"]);
+    }
+
+    #[test]
+    fn template_variables_allow_spaces_and_require_values() {
+        let vars = HashMap::from([
+            ("lang".to_string(), "Rust".to_string()),
+            ("file.name".to_string(), "main.rs".to_string()),
+        ]);
+        assert_eq!(
+            resolve_template_variables("Review {{ file.name }} in {{lang}}, not ${lang}", &vars)
+                .unwrap(),
+            "Review main.rs in Rust, not ${lang}"
+        );
+
+        let error = resolve_template_variables("{{lang}} {{topic}} {{tone}}", &vars).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Missing template variables: topic, tone")
+        );
+    }
+
+    #[test]
+    fn mask_secret_keeps_only_the_ends_of_long_secrets() {
+        assert_eq!(mask_secret("sk-ant-api03-abcdefgh"), "sk-a…efgh");
+        assert_eq!(mask_secret("short"), "*****");
+        assert_eq!(mask_secret("twelve-chars"), "********");
+        assert_eq!(mask_secret(""), "");
+    }
+
+    #[test]
+    fn mask_secrets_masks_credential_keys_but_not_references() {
+        let mut value: toml::Value = toml::from_str(
+            r#"
+url = "https://api.example/"
+api_key = "sk-0123456789abcdef"
+anthropic_api_key = "${ANTHROPIC_API_KEY}"
+
+[headers]
+Authorization = "Bearer 0123456789abcdef"
+X-Team = "platform"
+X-Auth-Token = "keyring:token"
+"#,
+        )
+        .unwrap();
+        mask_secrets(&mut value);
+
+        assert_eq!(value["url"].as_str(), Some("https://api.example/"));
+        assert_eq!(value["api_key"].as_str(), Some("sk-0…cdef"));
+        assert_eq!(
+            value["anthropic_api_key"].as_str(),
+            Some("${ANTHROPIC_API_KEY}")
+        );
+        assert_eq!(
+            value["headers"]["Authorization"].as_str(),
+            Some("Bear…cdef")
+        );
+        assert_eq!(value["headers"]["X-Team"].as_str(), Some("platform"));
+        assert_eq!(
+            value["headers"]["X-Auth-Token"].as_str(),
+            Some("keyring:token")
+        );
+    }
 }
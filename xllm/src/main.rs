@@ -1,21 +1,69 @@
 // mod genconfig;
+mod auth;
+mod cache;
+mod chat;
 mod genconfig;
 mod models;
+mod schema;
+mod session;
+mod templates;
+mod tools;
+mod transport;
+mod usage;
 mod utils;
 use anyhow::{Context, Result};
+use cache::CacheMode;
 use clap::{Arg, Command};
-use genconfig::{create_default_config, load_config, get_model_config, ModelProvider};
-use indicatif::{ProgressBar, ProgressStyle};
-use models::claude::call_claude_api;
-use utils::proxy::{proxy_config_true, call_claude_via_tcp_proxy};
+use genconfig::check::check_config;
+use genconfig::{
+    aliases_for, create_default_config, edit_config, get_model_config, load_config,
+    model_help, print_config_paths, show_config,
+};
+use models::{ContentBlock, Message, print_models, provider_for};
+use schema::{JsonSchema, send_with_schema};
+use session::{Session, print_sessions};
+use templates::{load_template, parse_var, render_template};
+use tools::{Interrupted, send_with_tools, tool_definitions};
+use transport::{Timeouts, transport_for};
+use utils::attachments::{load_image, load_pdf};
+use utils::context::{ContextBudget, append_stdin_content, collect_files, read_piped_stdin};
+use std::collections::HashMap;
 use std::fs;
-use utils::render::render_markdown;
+use std::io::{IsTerminal, Write};
+use utils::render::{OutputFormat, new_spinner, print_json_reply, render_markdown};
+
+/// Exit status after Ctrl-C, following the 128 + SIGINT convention
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// The profile named by `--profile`, or else `XLLM_PROFILE`. Read ahead of
+/// argument parsing because the -m help lists the profile's aliases.
+fn selected_profile() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--profile" {
+            return args.next();
+        }
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_string());
+        }
+    }
+    env_profile()
+}
+
+fn env_profile() -> Option<String> {
+    std::env::var("XLLM_PROFILE").ok().filter(|name| !name.is_empty())
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let model_help = model_help(selected_profile().as_deref());
+
     let matches = Command::new("xllm")
         .version("1.0")
-        .about("CLI tool for Claude API with markdown output")
+        .about("CLI tool for Claude, OpenAI and local Ollama models with markdown output")
         .arg(
             Arg::new("init")
                 .long("init")
@@ -24,16 +72,86 @@ async fn main() -> Result<()> {
         )
         .arg(
             Arg::new("prompt")
-                .help("The prompt to send to Claude")
-                .required_unless_present("init")
+                .help("The prompt to send, or - to read it from stdin. Text piped or redirected from a file is appended as context")
+                .required_unless_present_any(["init", "template"])
                 .index(1),
         )
+        .subcommand(
+            Command::new("chat").about("Start an interactive multi-turn chat session"),
+        )
+        .subcommand(Command::new("sessions").about("List saved conversation sessions"))
+        .subcommand(
+            Command::new("models")
+                .about("List the models available from the selected provider and the aliases for them"),
+        )
+        .subcommand(
+            Command::new("usage")
+                .about("Report token usage and spend by day and model")
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .value_name("N")
+                        .help("How many days back to report")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("30"),
+                ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Manage the local response cache")
+                .subcommand_required(true)
+                .subcommand(Command::new("clear").about("Delete every cached response"))
+                .subcommand(Command::new("stats").about("Show cache size and hit rate")),
+        )
+        .subcommand(
+            Command::new("auth")
+                .about("Manage API keys kept in the OS keyring, used in config as keyring:<name>")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Store a secret, read from stdin or prompted for")
+                        .arg(Arg::new("name").required(true).help("Name to store it under")),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete a stored secret")
+                        .arg(Arg::new("name").required(true).help("Name it was stored under")),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect and edit the configuration file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("check")
+                        .about("Validate the config and report problems by line"),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Print the effective config with secrets masked"),
+                )
+                .subcommand(
+                    Command::new("path").about("Show where the config is looked for and which file is used"),
+                )
+                .subcommand(
+                    Command::new("edit").about("Open the config in $VISUAL or $EDITOR, then check it"),
+                ),
+        )
+        .subcommand_negates_reqs(true)
         .arg(
             Arg::new("model")
                 .short('m')
                 .long("model")
                 .value_name("MODEL")
-                .help("Claude model to use: opus4, sonnet4, sonnet3, haiku3"),
+                .help(model_help)
+                .global(true),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Config profile to apply from [profiles.<name>] (default: $XLLM_PROFILE)")
+                .global(true),
         )
         .arg(
             Arg::new("max-tokens")
@@ -41,13 +159,142 @@ async fn main() -> Result<()> {
                 .long("max-tokens")
                 .value_name("TOKENS")
                 .help("Maximum tokens in response (overrides config default)")
-                .value_parser(clap::value_parser!(u32)),
+                .value_parser(clap::value_parser!(u32))
+                .global(true),
+        )
+        .arg(
+            Arg::new("template")
+                .short('T')
+                .long("template")
+                .value_name("NAME")
+                .help("Use a prompt template from [templates.<name>] or the templates directory"),
+        )
+        .arg(
+            Arg::new("var")
+                .long("var")
+                .value_name("NAME=VALUE")
+                .help("Value for a {{NAME}} template placeholder (repeatable)")
+                .value_parser(parse_var)
+                .action(clap::ArgAction::Append)
+                .requires("template"),
         )
         .arg(
             Arg::new("file")
+                .short('f')
                 .long("file")
-                .value_name("FILE")
-                .help("File to include in the prompt"),
+                .value_name("PATH")
+                .help("File, directory or glob to include in the prompt (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("image")
+                .long("image")
+                .value_name("PATH")
+                .help("Image to attach (PNG, JPEG, GIF or WebP, repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("doc")
+                .long("doc")
+                .value_name("PATH")
+                .help("PDF document to attach (repeatable)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("json-schema")
+                .long("json-schema")
+                .value_name("PATH")
+                .help("Ask for JSON matching this schema, validate it (retrying on failure) and print only the JSON"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FORMAT")
+                .help("Output format: markdown, raw or json (default: markdown on a terminal, raw otherwise). An explicit markdown waits for the full response unless --stream is also given")
+                .value_parser(["markdown", "raw", "json"]),
+        )
+        .arg(
+            Arg::new("count-tokens")
+                .long("count-tokens")
+                .help("Print the number of input tokens the prompt would use instead of sending it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("stream")
+                .long("stream")
+                .help("Stream the response as it is generated (default when stdout is a terminal). Streamed text is printed raw, even with -o markdown")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("no-stream")
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-stream")
+                .long("no-stream")
+                .help("Wait for the full response and render it as markdown")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("system")
+                .long("system")
+                .value_name("TEXT")
+                .help("System prompt (overrides the model's configured `system`)")
+                .conflicts_with("system-file")
+                .global(true),
+        )
+        .arg(
+            Arg::new("system-file")
+                .long("system-file")
+                .value_name("PATH")
+                .help("Read the system prompt from a file")
+                .global(true),
+        )
+        .arg(
+            Arg::new("tools")
+                .long("tools")
+                .help("Let Claude call the local command tools defined in [tools]")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("usage")
+                .long("usage")
+                .help("Print token counts and cost after each response")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .long("no-cache")
+                .help("Neither read nor write the response cache")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("refresh")
+                .global(true),
+        )
+        .arg(
+            Arg::new("refresh")
+                .long("refresh")
+                .help("Ignore cached responses and cache the new one")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("continue")
+                .short('c')
+                .long("continue")
+                .help("Continue the most recent conversation")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("resume")
+                .global(true),
+        )
+        .arg(
+            Arg::new("resume")
+                .short('r')
+                .long("resume")
+                .value_name("SESSION_ID")
+                .help("Continue a saved conversation by id (see `xllm sessions`)")
+                .global(true),
         )
         .get_matches();
 
@@ -62,76 +309,324 @@ async fn main() -> Result<()> {
         }
     }
 
-    let prompt = matches.get_one::<String>("prompt").unwrap();
+    // Errors are reported by the commands that need a config
+    let profile = matches
+        .get_one::<String>("profile")
+        .cloned()
+        .or_else(env_profile);
+    let config = load_config(profile.as_deref());
+
     let model_str = matches.get_one::<String>("model").map(|s| s.as_str());
     let max_tokens_override = matches.get_one::<u32>("max-tokens").copied();
-    let file_path = matches.get_one::<String>("file");
+    // Asking for markdown explicitly means waiting to render it, unless
+    // --stream says otherwise
+    let explicit_markdown = matches
+        .get_one::<String>("output")
+        .is_some_and(|output| output == "markdown");
+    let stream = if matches.get_flag("stream") {
+        true
+    } else {
+        !matches.get_flag("no-stream") && !explicit_markdown && std::io::stdout().is_terminal()
+    };
 
-    let model_override = models::claude::parse_model(model_str);
+    // A system prompt from the command line replaces the configured one
+    let system = match matches.get_one::<String>("system-file") {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read system prompt file: {}", path))?
+                .trim_end()
+                .to_string(),
+        ),
+        None => matches.get_one::<String>("system").cloned(),
+    };
 
-    // Build the final prompt
-    let mut final_prompt = prompt.clone();
+    // Handle sessions subcommand
+    if matches.subcommand_matches("sessions").is_some() {
+        return print_sessions();
+    }
+
+    let cache_mode = CacheMode::from_flags(matches.get_flag("no-cache"), matches.get_flag("refresh"));
+
+    // Handle cache subcommand
+    if let Some(cache_matches) = matches.subcommand_matches("cache") {
+        return match cache_matches.subcommand_name() {
+            Some("clear") => cache::clear_cache(),
+            _ => {
+                let config = config.context("Failed to load configuration")?;
+                cache::print_stats(&config)
+            }
+        };
+    }
+
+    // Handle auth subcommand
+    if let Some(auth_matches) = matches.subcommand_matches("auth") {
+        return match auth_matches.subcommand() {
+            Some(("set", set_matches)) => {
+                auth::set_secret(set_matches.get_one::<String>("name").unwrap())
+            }
+            Some(("remove", remove_matches)) => {
+                auth::remove_secret(remove_matches.get_one::<String>("name").unwrap())
+            }
+            _ => Ok(()),
+        };
+    }
+
+    // Handle config subcommand
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        return match config_matches.subcommand_name() {
+            Some("check") => check_config(config.as_ref(), profile.as_deref()).await,
+            Some("show") => {
+                let config = config.context("Failed to load configuration")?;
+                show_config(&config, profile.as_deref())
+            }
+            Some("edit") => {
+                edit_config()?;
+                // Check what was just saved rather than the config loaded at startup
+                let config = load_config(profile.as_deref());
+                check_config(config.as_ref(), profile.as_deref()).await
+            }
+            _ => print_config_paths(),
+        };
+    }
+
+    // Handle usage subcommand
+    if let Some(usage_matches) = matches.subcommand_matches("usage") {
+        return usage::print_report(*usage_matches.get_one::<u64>("days").unwrap_or(&30));
+    }
 
-    if let Some(file_path) = file_path {
-        let file_content = fs::read_to_string(file_path)
-            .with_context(|| format!("Failed to read file: {}", file_path))?;
+    // Handle models subcommand
+    if matches.subcommand_matches("models").is_some() {
+        let config = config.context("Failed to load configuration")?;
+        let model_provider = get_model_config(&config, model_str)?;
+        let transport = transport_for(
+            &config,
+            Timeouts::for_model(&config, &model_provider),
+            cache_mode,
+        )?;
+        let aliases = aliases_for(&config, &model_provider);
+        let provider = provider_for(model_provider)?;
 
-        final_prompt = format!("{}\n\nFile content:\n```\n{}\n```", prompt, file_content);
+        return print_models(provider.as_ref(), transport.as_ref(), &aliases).await;
     }
 
-    let config = load_config().context("Failed to load configuration")?;
-    
-    // Determine which model to use - either from command line or default
-    let model_name = if let Some(model_str) = model_str {
-        model_str
+    // Pick up a previous conversation or start a new one
+    let mut session = if matches.get_flag("continue") {
+        Session::load_latest()?
+    } else if let Some(id) = matches.get_one::<String>("resume") {
+        Session::load(id)?
     } else {
-        "sonnet4" // Default model
+        Session::new()
+    };
+
+    // Handle chat subcommand
+    if matches.subcommand_matches("chat").is_some() {
+        let config = config.context("Failed to load configuration")?;
+        let options = chat::ChatOptions {
+            max_tokens_override,
+            system,
+            tools: matches.get_flag("tools"),
+            stream,
+            show_usage: matches.get_flag("usage"),
+            cache_mode,
+        };
+        return chat::run_chat(&config, model_str, options, session).await;
+    }
+
+    let config = config.context("Failed to load configuration")?;
+
+    // A template supplies the prompt, and defaults for model, max_tokens and
+    // system that the command line overrides
+    let template = match matches.get_one::<String>("template") {
+        Some(name) => {
+            let vars: HashMap<String, String> = matches
+                .get_many::<(String, String)>("var")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let template = load_template(&config, name)?;
+            Some(
+                render_template(&template, &vars)
+                    .with_context(|| format!("Failed to render template: {}", name))?,
+            )
+        }
+        None => None,
+    };
+    let model_str = model_str.or(template.as_ref().and_then(|t| t.model.as_deref()));
+    let max_tokens_override =
+        max_tokens_override.or(template.as_ref().and_then(|t| t.max_tokens));
+    let system = system.or(template.as_ref().and_then(|t| t.system.clone()));
+
+    // Build the final prompt. Piped stdin is either the prompt itself (`-`)
+    // or extra context appended after it.
+    let prompt_arg = matches.get_one::<String>("prompt").map(String::as_str);
+    let mut piped_input = read_piped_stdin(prompt_arg == Some("-"))?;
+    let prompt = match prompt_arg {
+        Some("-") => Some(piped_input.take().ok_or_else(|| {
+            anyhow::anyhow!("No prompt on stdin: pipe text in when using `-` as the prompt")
+        })?),
+        Some(prompt) => Some(prompt.to_string()),
+        None => None,
     };
-    
-    let model_provider = get_model_config(&config, model_name)
-        .with_context(|| format!("Failed to get configuration for model: {}", model_name))?;
-    
-    let claude_config = match model_provider {
-        ModelProvider::Claude(config) => config,
-        // Future providers can be handled here
+    let mut final_prompt = match (&template, prompt) {
+        (Some(template), Some(prompt)) => format!("{}\n\n{}", template.prompt, prompt),
+        (Some(template), None) => template.prompt.clone(),
+        (None, Some(prompt)) => prompt,
+        (None, None) => return Err(anyhow::anyhow!("A prompt or --template is required")),
     };
+    if let Some(input) = piped_input {
+        final_prompt = append_stdin_content(&final_prompt, &input);
+    }
+
+    let file_patterns: Vec<String> = matches
+        .get_many::<String>("file")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    if !file_patterns.is_empty() {
+        let context = collect_files(&file_patterns, ContextBudget::from_config(&config))?;
+        context.print_report();
+        final_prompt = context.append_to(&final_prompt);
+    }
 
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["🤖", "🔧", "⚙️", "🔩", "🤖", "⚡", "💻", "🧠"])
-            .template("{spinner} {msg}")
-            .unwrap(),
+    // Determine which model to use - either from command line or the configured default
+    let mut model_provider = get_model_config(&config, model_str).with_context(|| {
+        format!(
+            "Failed to get configuration for model: {}",
+            model_str.unwrap_or("default")
+        )
+    })?;
+    if let Some(system) = system {
+        model_provider.override_system(system);
+    }
+    if matches.get_flag("tools") {
+        model_provider.set_tools(tool_definitions(&config)?)?;
+    }
+    let json_schema = matches
+        .get_one::<String>("json-schema")
+        .map(|path| JsonSchema::load(path))
+        .transpose()?;
+    if let Some(schema) = &json_schema {
+        model_provider.append_system(&schema.instruction());
+    }
+    let transport = transport_for(
+        &config,
+        Timeouts::for_model(&config, &model_provider),
+        cache_mode,
+    )?;
+    let provider = provider_for(model_provider)?;
+
+    // Documents and images go before the text, as the API recommends
+    let mut content = Vec::new();
+    for path in matches.get_many::<String>("doc").into_iter().flatten() {
+        content.push(load_pdf(path)?);
+    }
+    for path in matches.get_many::<String>("image").into_iter().flatten() {
+        content.push(load_image(path)?);
+    }
+    content.push(ContentBlock::text(final_prompt));
+    session.messages.push(Message::user_blocks(content));
+
+    if matches.get_flag("count-tokens") {
+        let count = provider
+            .count_tokens(transport.as_ref(), &session.messages)
+            .await?;
+        println!("{}", count);
+        return Ok(());
+    }
+
+    let spinner = new_spinner();
+
+    let output = OutputFormat::from_arg(
+        matches.get_one::<String>("output").map(String::as_str),
+        std::io::stdout().is_terminal(),
     );
-    spinner.set_message("loading...");
-    spinner.enable_steady_tick(std::time::Duration::from_millis(200)); 
-    
-    // Check if proxy is enabled in config and decide which method to use
-    let result = if proxy_config_true(&config) {
-        // Use TCP proxy
-        call_claude_via_tcp_proxy(
-            &claude_config,
-            &config,
-            &final_prompt,
-            model_override,
-            max_tokens_override,
-        ).await
-    } else {
-        // Use direct Claude API
-        call_claude_api(
-            &claude_config,
-            &final_prompt,
-            model_override,
-            max_tokens_override,
-        ).await
+
+    // JSON output and schema-validated replies are printed whole, so they never stream
+    let print_text = json_schema.is_none() && output != OutputFormat::Json;
+    let streamed = stream && transport.supports_streaming() && print_text;
+    let mut on_text = |text: &str| {
+        if !spinner.is_finished() {
+            spinner.finish_and_clear();
+        }
+        if print_text {
+            print!("{}", text);
+            let _ = std::io::stdout().flush();
+        }
+    };
+    let send = async {
+        match &json_schema {
+            Some(schema) => send_with_schema(
+                provider.as_ref(),
+                transport.as_ref(),
+                &config.tools,
+                schema,
+                &mut session.messages,
+                max_tokens_override,
+                &mut on_text,
+            )
+            .await
+            .map(|(reply, value)| (reply, Some(value))),
+            None => send_with_tools(
+                provider.as_ref(),
+                transport.as_ref(),
+                &config.tools,
+                &mut session.messages,
+                max_tokens_override,
+                streamed,
+                &mut on_text,
+            )
+            .await
+            .map(|reply| (reply, None)),
+        }
+    };
+
+    // Dropping the request on Ctrl-C closes its connection
+    let result = tokio::select! {
+        result = send => Some(result),
+        _ = tokio::signal::ctrl_c() => None,
+    };
+    // Ctrl-C at a tool confirmation is a keypress, not a signal
+    let result = result.filter(|result| !matches!(result, Err(e) if e.is::<Interrupted>()));
+    let Some(result) = result else {
+        spinner.finish_and_clear();
+        if streamed {
+            // Keep the partial response that was already printed
+            println!();
+        }
+        eprintln!("⚠️  Interrupted");
+        std::process::exit(EXIT_INTERRUPTED);
     };
 
     match result {
-        Ok(response) => {
+        Ok((reply, json)) => {
             spinner.finish_and_clear();
 
-            // Render the response as markdown
-            render_markdown(&response);
+            if let Some(json) = json {
+                // Only the validated JSON goes to stdout
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            } else if output == OutputFormat::Json {
+                print_json_reply(&reply, &session.id)?;
+            } else if streamed {
+                // Text was already printed as it arrived
+                println!();
+            } else if output == OutputFormat::Raw {
+                println!("{}", reply.text);
+            } else {
+                // Render the response as markdown
+                render_markdown(&reply.text);
+            }
+
+            usage::record_usage(
+                &config,
+                &reply,
+                provider.model(),
+                matches.get_flag("usage"),
+                transport.served_from_cache(),
+            );
+            session.record(&reply);
+            if let Err(e) = session.save() {
+                eprintln!("⚠️  Failed to save session: {}", e);
+            }
         }
         Err(e) => {
             spinner.finish_and_clear();
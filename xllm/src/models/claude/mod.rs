@@ -1,126 +1,495 @@
+use super::{LlmProvider, Message, ModelInfo, Reply, ToolCall, Usage};
+use crate::transport::{HttpRequest, Transport};
+use crate::utils::date::date_part;
+use crate::utils::sse::SseDecoder;
 use anyhow::{Context, Result};
-use reqwest::Client;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
-pub enum ClaudeModels {
-    Opus4,
-    Sonnet4,
-    Sonnet3_7,
-    Haiku3_5,
-}
-
-impl ClaudeModels {
-    pub fn to_string(&self) -> String {
-        match self {
-            ClaudeModels::Opus4 => "claude-opus-4-20250514".to_string(),
-            ClaudeModels::Sonnet4 => "claude-sonnet-4-20250514".to_string(),
-            ClaudeModels::Sonnet3_7 => "claude-3-7-sonnet-latest".to_string(),
-            ClaudeModels::Haiku3_5 => "claude-3-5-haiku-latest".to_string(),
-        }
-    }
+#[derive(Serialize)]
+pub struct ClaudeRequest {
+    pub model: String,
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+}
+
+/// A tool the model may call, as sent in the request's `tools` list
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub input_schema: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct ClaudeResponse {
+    #[serde(default)]
+    pub model: String,
+    pub content: Vec<ResponseBlock>,
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+    #[serde(default)]
+    pub usage: Usage,
 }
 
-pub fn parse_model(name: Option<&str>) -> Option<ClaudeModels> {
-    match name {
-        Some("opus4") => Some(ClaudeModels::Opus4),
-        Some("sonnet4") => Some(ClaudeModels::Sonnet4),
-        Some("sonnet3") => Some(ClaudeModels::Sonnet3_7),
-        Some("haiku3") => Some(ClaudeModels::Haiku3_5),
-        Some(invalid) => {
-            eprintln!(
-                "❌ Invalid model '{}'. Available: opus4, sonnet4, sonnet3, haiku3",
-                invalid
-            );
-            std::process::exit(1);
+impl ClaudeResponse {
+    pub fn into_reply(self) -> Result<Reply> {
+        let mut reply = Reply {
+            model: self.model,
+            usage: self.usage,
+            stop_reason: self.stop_reason,
+            ..Reply::default()
+        };
+
+        for block in self.content {
+            match block {
+                ResponseBlock::Text { text } => reply.text.push_str(&text),
+                ResponseBlock::ToolUse { id, name, input } => {
+                    reply.tool_calls.push(ToolCall { id, name, input })
+                }
+                ResponseBlock::Other => {}
+            }
+        }
+
+        if reply.text.is_empty() && reply.tool_calls.is_empty() {
+            return Err(anyhow::anyhow!("No content in Claude response"));
         }
-        None => None,
+        Ok(reply)
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Events sent by `/v1/messages` when `"stream": true` is set
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    MessageStart {
+        message: StreamMessage,
+    },
+    ContentBlockStart {
+        content_block: ResponseBlock,
+    },
+    ContentBlockDelta {
+        delta: StreamDelta,
+    },
+    ContentBlockStop,
+    MessageDelta {
+        delta: MessageDeltaBody,
+        #[serde(default)]
+        usage: Usage,
+    },
+    MessageStop,
+    Ping,
+    Error { error: StreamError },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamDelta {
+    TextDelta { text: String },
+    InputJsonDelta { partial_json: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageDeltaBody {
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+}
+
 #[derive(Serialize)]
-pub struct ClaudeRequest {
+pub struct CountTokensRequest {
     pub model: String,
-    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
     pub messages: Vec<Message>,
 }
 
-#[derive(Serialize)]
-pub struct Message {
-    pub role: String,
-    pub content: String,
+#[derive(Deserialize)]
+pub struct CountTokensResponse {
+    pub input_tokens: u64,
 }
 
 #[derive(Deserialize)]
-pub struct ClaudeResponse {
-    pub content: Vec<ContentBlock>,
+pub struct ModelsResponse {
+    pub data: Vec<ModelEntry>,
+    #[serde(default)]
+    pub has_more: bool,
+    #[serde(default)]
+    pub last_id: Option<String>,
 }
 
 #[derive(Deserialize)]
-pub struct ContentBlock {
-    pub text: String,
+pub struct ModelEntry {
+    pub id: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
 }
 
-pub async fn call_claude_api(
-    config: &crate::genconfig::ClaudeConfig,
-    prompt: &str,
-    model_override: Option<ClaudeModels>,
-    max_tokens_override: Option<u32>,
-) -> Result<String> {
-    let client = Client::new();
+#[derive(Debug, Deserialize)]
+pub struct StreamMessage {
+    pub model: String,
+    #[serde(default)]
+    pub usage: Usage,
+}
 
-    let model = if let Some(model_enum) = model_override {
-        model_enum.to_string()
-    } else {
-        config.model.clone()
-    };
+#[derive(Debug, Deserialize)]
+pub struct StreamError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
 
+pub fn build_request(
+    config: &crate::genconfig::ClaudeConfig,
+    messages: &[Message],
+    max_tokens_override: Option<u32>,
+    stream: bool,
+) -> ClaudeRequest {
     let max_tokens = max_tokens_override.unwrap_or(config.max_tokens);
 
-    let request = ClaudeRequest {
-        model,
+    ClaudeRequest {
+        model: config.model.clone(),
         max_tokens,
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }],
-    };
+        system: config.system.clone(),
+        messages: messages.to_vec(),
+        tools: config.tools.clone(),
+        stream: stream.then_some(true),
+    }
+}
 
-    // Prepare headers for the API request
+pub fn build_headers(config: &crate::genconfig::ClaudeConfig) -> HashMap<String, String> {
     let mut headers = HashMap::new();
     headers.insert("Content-Type".to_string(), "application/json".to_string());
     headers.insert("x-api-key".to_string(), config.anthropic_api_key.clone());
     headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
+    headers
+}
+
+pub fn messages_url(config: &crate::genconfig::ClaudeConfig) -> String {
+    format!("{}/v1/messages", config.url)
+}
+
+/// Anthropic Messages API provider
+pub struct ClaudeProvider {
+    config: crate::genconfig::ClaudeConfig,
+}
 
-    // Perform direct HTTP request to Claude API
-    let response = client
-        .post(&format!("{}/v1/messages", config.url))
-        .headers(reqwest::header::HeaderMap::from_iter(
-            headers.iter().map(|(k, v)| {
-                (reqwest::header::HeaderName::from_bytes(k.as_bytes()).unwrap(),
-                 reqwest::header::HeaderValue::from_str(v).unwrap())
+impl ClaudeProvider {
+    pub fn new(config: crate::genconfig::ClaudeConfig) -> Self {
+        ClaudeProvider { config }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ClaudeProvider {
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn complete(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+    ) -> Result<Reply> {
+        let request = build_request(&self.config, messages, max_tokens_override, false);
+        let http_request = HttpRequest::post_json(
+            messages_url(&self.config),
+            build_headers(&self.config),
+            &request,
+        )?;
+
+        let response = transport.send(&http_request).await?;
+
+        let claude_response: ClaudeResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse Claude API response")?;
+
+        claude_response.into_reply()
+    }
+
+    async fn stream(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+        on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+    ) -> Result<Reply> {
+        let request = build_request(&self.config, messages, max_tokens_override, true);
+        let http_request = HttpRequest::post_json(
+            messages_url(&self.config),
+            build_headers(&self.config),
+            &request,
+        )?;
+
+        let mut decoder = SseDecoder::default();
+        let mut reply = Reply {
+            model: request.model.clone(),
+            ..Reply::default()
+        };
+        // Tool input arrives as JSON fragments for the most recent tool_use block
+        let mut tool_input = String::new();
+
+        transport
+            .send_streaming(&http_request, &mut |chunk| {
+                for data in decoder.push(chunk) {
+                    let event: StreamEvent = serde_json::from_str(&data)
+                        .with_context(|| format!("Failed to parse stream event: {}", data))?;
+
+                    match event {
+                        StreamEvent::MessageStart { message } => {
+                            reply.model = message.model;
+                            reply.usage = message.usage;
+                        }
+                        StreamEvent::ContentBlockStart {
+                            content_block: ResponseBlock::ToolUse { id, name, input },
+                        } => {
+                            reply.tool_calls.push(ToolCall { id, name, input });
+                            tool_input.clear();
+                        }
+                        StreamEvent::ContentBlockDelta {
+                            delta: StreamDelta::TextDelta { text },
+                        } => {
+                            on_text(&text);
+                            reply.text.push_str(&text);
+                        }
+                        StreamEvent::ContentBlockDelta {
+                            delta: StreamDelta::InputJsonDelta { partial_json },
+                        } => tool_input.push_str(&partial_json),
+                        StreamEvent::ContentBlockStop => {
+                            if let Some(call) = reply.tool_calls.last_mut()
+                                && !tool_input.is_empty()
+                            {
+                                call.input = serde_json::from_str(&tool_input)
+                                    .context("Failed to parse streamed tool input")?;
+                                tool_input.clear();
+                            }
+                        }
+                        StreamEvent::MessageDelta { delta, usage } => {
+                            reply.stop_reason = delta.stop_reason;
+                            // Counts here are cumulative; zero means not reported
+                            reply.usage.output_tokens = usage.output_tokens;
+                            if usage.input_tokens > 0 {
+                                reply.usage.input_tokens = usage.input_tokens;
+                            }
+                            if usage.cache_creation_input_tokens > 0 {
+                                reply.usage.cache_creation_input_tokens =
+                                    usage.cache_creation_input_tokens;
+                            }
+                            if usage.cache_read_input_tokens > 0 {
+                                reply.usage.cache_read_input_tokens = usage.cache_read_input_tokens;
+                            }
+                        }
+                        StreamEvent::Error { error } => {
+                            return Err(anyhow::anyhow!(
+                                "API stream error ({}): {}",
+                                error.kind,
+                                error.message
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(())
             })
+            .await?;
+
+        if reply.text.is_empty() && reply.tool_calls.is_empty() {
+            Err(anyhow::anyhow!("No content in Claude response"))
+        } else {
+            Ok(reply)
+        }
+    }
+
+    async fn count_tokens(&self, transport: &dyn Transport, messages: &[Message]) -> Result<u64> {
+        let request = CountTokensRequest {
+            model: self.config.model.clone(),
+            system: self.config.system.clone(),
+            messages: messages.to_vec(),
+        };
+        let http_request = HttpRequest::post_json(
+            format!("{}/v1/messages/count_tokens", self.config.url),
+            build_headers(&self.config),
+            &request,
+        )?;
+
+        let response = transport.send(&http_request).await?;
+
+        let count: CountTokensResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse Claude token count response")?;
+
+        Ok(count.input_tokens)
+    }
+
+    async fn list_models(&self, transport: &dyn Transport) -> Result<Vec<ModelInfo>> {
+        let mut models = Vec::new();
+        let mut after_id: Option<String> = None;
+
+        // The endpoint is paginated; follow `last_id` until `has_more` is false
+        loop {
+            let mut url = format!("{}/v1/models?limit=1000", self.config.url);
+            if let Some(after_id) = &after_id {
+                url.push_str(&format!("&after_id={}", after_id));
+            }
+            let http_request = HttpRequest::get(url, build_headers(&self.config));
+
+            let response = transport.send(&http_request).await?;
+
+            let page: ModelsResponse = serde_json::from_slice(&response.body)
+                .context("Failed to parse Claude models response")?;
+
+            models.extend(page.data.into_iter().map(|model| ModelInfo {
+                id: model.id,
+                display_name: model.display_name,
+                created: model.created_at.as_deref().map(date_part),
+            }));
+
+            match page.last_id {
+                Some(last_id) if page.has_more => after_id = Some(last_id),
+                _ => return Ok(models),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::stub::{request_body, serve_once, transport};
+
+    fn provider(url: &str) -> ClaudeProvider {
+        let config = toml::from_str(&format!(
+            r#"
+model = "claude-test"
+max_tokens = 64
+url = "{}"
+anthropic_api_key = "sk-test"
+"#,
+            url
         ))
-        .json(&request)
-        .send()
-        .await
-        .context("Failed to send request to Claude API")?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(anyhow::anyhow!("API request failed: {}", error_text));
+        .unwrap();
+        ClaudeProvider::new(config)
+    }
+
+    #[tokio::test]
+    async fn complete_sends_the_conversation_and_parses_the_reply() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![
+                r#"{"model":"claude-test-1","content":[{"type":"text","text":"Hello!"}],"stop_reason":"end_turn","usage":{"input_tokens":9,"output_tokens":3}}"#,
+            ],
+        )
+        .await;
+
+        let reply = provider(&url)
+            .complete(&transport(), &[Message::user("Hi")], Some(32))
+            .await
+            .unwrap();
+        assert_eq!(reply.text, "Hello!");
+        assert_eq!(reply.model, "claude-test-1");
+        assert_eq!(reply.stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (9, 3)
+        );
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /v1/messages HTTP/1.1"));
+        assert!(request.contains("x-api-key: sk-test"));
+        let body = request_body(&request);
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["max_tokens"], 32);
+        assert_eq!(body["messages"][0]["content"][0]["text"], "Hi");
+        assert!(body.get("stream").is_none());
+    }
+
+    #[tokio::test]
+    async fn complete_reports_api_errors() {
+        let (url, _) = serve_once(
+            "401 Unauthorized",
+            vec![r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#],
+        )
+        .await;
+
+        let error = provider(&url)
+            .complete(&transport(), &[Message::user("Hi")], None)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("401"));
+    }
+
+    #[tokio::test]
+    async fn stream_hands_on_text_deltas_across_chunks() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![
+                "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"model\":\"claude-test-1\",\"usage\":{\"input_tokens\":9,\"output_tokens\":1}}}\n\n",
+                "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hel",
+                "lo\"}}\r\n\r\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" there\"}}\n\n",
+                "data: {\"type\":\"content_block_stop\",\"index\":0}\n\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":4}}\n\ndata: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )
+        .await;
+
+        let mut deltas = Vec::new();
+        let reply = provider(&url)
+            .stream(&transport(), &[Message::user("Hi")], None, &mut |text| {
+                deltas.push(text.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(deltas, ["Hello", " there"]);
+        assert_eq!(reply.text, "Hello there");
+        assert_eq!(reply.model, "claude-test-1");
+        assert_eq!(reply.stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (9, 4)
+        );
+
+        let request = request.await.unwrap();
+        let body = request_body(&request);
+        assert_eq!(body["stream"], true);
     }
 
-    let claude_response: ClaudeResponse = response
-        .json()
-        .await
-        .context("Failed to parse Claude API response")?;
+    #[tokio::test]
+    async fn stream_reports_error_events() {
+        let (url, _) = serve_once(
+            "200 OK",
+            vec!["data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n"],
+        )
+        .await;
 
-    // Extract text from the first content block
-    if let Some(content_block) = claude_response.content.first() {
-        Ok(content_block.text.clone())
-    } else {
-        Err(anyhow::anyhow!("No content in Claude response"))
+        let error = provider(&url)
+            .stream(&transport(), &[Message::user("Hi")], None, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("API stream error (overloaded_error): Overloaded"));
     }
 }
@@ -1 +1,307 @@
 pub mod claude;
+pub mod ollama;
+pub mod openai;
+#[cfg(test)]
+pub mod scripted;
+
+use crate::genconfig::ModelProvider;
+use crate::transport::Transport;
+use anyhow::Result;
+use async_trait::async_trait;
+use claude::ClaudeProvider;
+use ollama::OllamaProvider;
+use openai::OpenAIProvider;
+use serde::{Deserialize, Serialize};
+
+/// A conversation turn. Content is a list of blocks in the Anthropic shape;
+/// other providers convert it to their own wire format.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    pub role: String,
+    #[serde(deserialize_with = "deserialize_content")]
+    pub content: Vec<ContentBlock>,
+}
+
+impl Message {
+    pub fn user(content: impl Into<String>) -> Self {
+        Message::user_blocks(vec![ContentBlock::text(content)])
+    }
+
+    pub fn user_blocks(content: Vec<ContentBlock>) -> Self {
+        Message {
+            role: "user".to_string(),
+            content,
+        }
+    }
+
+    /// The text blocks of the message joined together
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Base64 images attached to the message
+    pub fn images(&self) -> impl Iterator<Item = &MediaSource> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Image { source } => Some(source),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text { text: String },
+    Image { source: MediaSource },
+    Document {
+        source: MediaSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
+    },
+}
+
+impl ContentBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentBlock::Text { text: text.into() }
+    }
+}
+
+/// Inline base64 data for image and document blocks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MediaSource {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub media_type: String,
+    pub data: String,
+}
+
+impl MediaSource {
+    pub fn base64(media_type: impl Into<String>, data: String) -> Self {
+        MediaSource {
+            kind: "base64".to_string(),
+            media_type: media_type.into(),
+            data,
+        }
+    }
+
+    /// `data:` URL form used by the OpenAI API
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.media_type, self.data)
+    }
+}
+
+/// Accept both content-block arrays and the plain strings saved by older sessions
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<ContentBlock>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Content {
+        Text(String),
+        Blocks(Vec<ContentBlock>),
+    }
+
+    Ok(match Content::deserialize(deserializer)? {
+        Content::Text(text) => vec![ContentBlock::text(text)],
+        Content::Blocks(blocks) => blocks,
+    })
+}
+
+/// Token counts reported by the API for a single request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens served from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+/// A completed response: its text, the model that produced it and its token usage
+#[derive(Debug, Clone, Default)]
+pub struct Reply {
+    pub text: String,
+    pub model: String,
+    pub usage: Usage,
+    /// Why generation stopped (`end_turn`, `max_tokens`, `tool_use`, ...)
+    pub stop_reason: Option<String>,
+    /// Tools the model asked to run before it can continue
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// A `tool_use` request from the model
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub input: serde_json::Value,
+}
+
+impl Reply {
+    /// The assistant turn to append to the conversation, including any tool calls
+    pub fn to_message(&self) -> Message {
+        let mut content = Vec::new();
+        if !self.text.is_empty() {
+            content.push(ContentBlock::text(self.text.clone()));
+        }
+        content.extend(self.tool_calls.iter().map(|call| ContentBlock::ToolUse {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            input: call.input.clone(),
+        }));
+        Message {
+            role: "assistant".to_string(),
+            content,
+        }
+    }
+}
+
+/// A model listed by a provider's models endpoint
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub created: Option<String>,
+}
+
+/// A chat API dialect. Providers build requests and parse responses, while the
+/// `Transport` decides how those requests reach the provider.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Model ID requests are sent for
+    fn model(&self) -> &str;
+
+    /// Send a conversation and wait for the full reply
+    async fn complete(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+    ) -> Result<Reply>;
+
+    /// Send a conversation, handing text deltas to `on_text` as they arrive
+    async fn stream(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+        on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+    ) -> Result<Reply>;
+
+    /// Count the input tokens a conversation would use
+    async fn count_tokens(&self, transport: &dyn Transport, messages: &[Message]) -> Result<u64>;
+
+    /// List the models available from the provider
+    async fn list_models(&self, transport: &dyn Transport) -> Result<Vec<ModelInfo>>;
+}
+
+/// Build the provider implementation for a resolved model configuration,
+/// resolving its credentials first
+pub fn provider_for(mut model_provider: ModelProvider) -> Result<Box<dyn LlmProvider>> {
+    model_provider.resolve_credentials()?;
+    Ok(match model_provider {
+        ModelProvider::Claude(config) => Box::new(ClaudeProvider::new(config)),
+        ModelProvider::OpenAI(config) => Box::new(OpenAIProvider::new(config)),
+        ModelProvider::Ollama(config) => Box::new(OllamaProvider::new(config)),
+    })
+}
+
+/// Print the provider's models with the local aliases that point at each,
+/// then any aliases whose model the provider did not list
+pub async fn print_models(
+    provider: &dyn LlmProvider,
+    transport: &dyn Transport,
+    aliases: &[(String, String)],
+) -> Result<()> {
+    let models = provider.list_models(transport).await?;
+    if models.is_empty() {
+        println!("No models available");
+        return Ok(());
+    }
+
+    let aliases_of = |id: &str| {
+        aliases
+            .iter()
+            .filter(|(_, model)| model == id)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    println!("{:<40}  {:<32}  {:<10}  ALIASES", "ID", "NAME", "CREATED");
+    for model in &models {
+        println!(
+            "{:<40}  {:<32}  {:<10}  {}",
+            model.id,
+            model.display_name.as_deref().unwrap_or_default(),
+            model.created.as_deref().unwrap_or_default(),
+            aliases_of(&model.id)
+        );
+    }
+
+    // e.g. `-latest` names, which the API accepts but does not list
+    let unlisted: Vec<_> = aliases
+        .iter()
+        .filter(|(_, model)| !models.iter().any(|listed| &listed.id == model))
+        .collect();
+    if !unlisted.is_empty() {
+        println!("\nAliases for models not in this list:");
+        for (name, model) in unlisted {
+            println!("  {:<12} → {}", name, model);
+        }
+    }
+    Ok(())
+}
+
+/// Send a conversation, streaming when requested and the transport supports it
+pub async fn send_messages(
+    provider: &dyn LlmProvider,
+    transport: &dyn Transport,
+    messages: &[Message],
+    max_tokens_override: Option<u32>,
+    stream: bool,
+    on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+) -> Result<Reply> {
+    if stream && transport.supports_streaming() {
+        provider
+            .stream(transport, messages, max_tokens_override, on_text)
+            .await
+    } else {
+        provider
+            .complete(transport, messages, max_tokens_override)
+            .await
+    }
+}
@@ -0,0 +1,406 @@
+use super::{ContentBlock, LlmProvider, Message, ModelInfo, Reply, Usage};
+use crate::transport::{HttpRequest, Transport};
+use crate::utils::date::date_part;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+pub struct OllamaRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<OllamaOptions>,
+}
+
+/// A chat message in the Ollama format, with images as bare base64 strings
+#[derive(Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+impl From<&Message> for ChatMessage {
+    fn from(message: &Message) -> Self {
+        ChatMessage {
+            role: message.role.clone(),
+            content: message.text(),
+            images: message.images().map(|source| source.data.clone()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct OllamaOptions {
+    pub num_predict: u32,
+}
+
+/// A full `/api/chat` response, or one line of a streamed response
+#[derive(Deserialize)]
+pub struct OllamaResponse {
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub message: Option<OllamaMessage>,
+    #[serde(default)]
+    pub done: bool,
+    #[serde(default)]
+    pub prompt_eval_count: u64,
+    #[serde(default)]
+    pub eval_count: u64,
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OllamaMessage {
+    #[serde(default)]
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagsResponse {
+    pub models: Vec<TagEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct TagEntry {
+    pub name: String,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+}
+
+impl OllamaResponse {
+    pub fn into_reply(self) -> Result<Reply> {
+        if let Some(error) = self.error {
+            return Err(anyhow::anyhow!("Ollama error: {}", error));
+        }
+
+        let text = self
+            .message
+            .map(|message| message.content)
+            .ok_or_else(|| anyhow::anyhow!("No content in Ollama response"))?;
+
+        Ok(Reply {
+            text,
+            model: self.model,
+            usage: Usage {
+                input_tokens: self.prompt_eval_count,
+                output_tokens: self.eval_count,
+                ..Usage::default()
+            },
+            stop_reason: self.done_reason,
+            ..Reply::default()
+        })
+    }
+}
+
+pub fn build_request(
+    config: &crate::genconfig::OllamaConfig,
+    messages: &[Message],
+    max_tokens_override: Option<u32>,
+    stream: bool,
+) -> OllamaRequest {
+    // The system prompt is sent as the first message
+    let system_message = config.system.clone().map(|system| ChatMessage {
+        role: "system".to_string(),
+        content: system,
+        images: Vec::new(),
+    });
+
+    OllamaRequest {
+        model: config.model.clone(),
+        messages: system_message
+            .into_iter()
+            .chain(messages.iter().map(ChatMessage::from))
+            .collect(),
+        stream,
+        options: max_tokens_override
+            .or(config.max_tokens)
+            .map(|num_predict| OllamaOptions { num_predict }),
+    }
+}
+
+/// Ollama's chat API only takes text and images
+fn check_supported(messages: &[Message]) -> Result<()> {
+    let has_documents = messages.iter().any(|message| {
+        message
+            .content
+            .iter()
+            .any(|block| matches!(block, ContentBlock::Document { .. }))
+    });
+    if has_documents {
+        return Err(anyhow::anyhow!(
+            "Document attachments are not supported by Ollama"
+        ));
+    }
+    Ok(())
+}
+
+pub fn build_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+    headers
+}
+
+pub fn chat_url(config: &crate::genconfig::OllamaConfig) -> String {
+    format!("{}/api/chat", config.url.trim_end_matches('/'))
+}
+
+/// Add one line of a streamed response to `reply`. Blank lines are skipped.
+fn apply_stream_line(
+    line: &[u8],
+    reply: &mut Reply,
+    on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+) -> Result<()> {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(());
+    }
+
+    let part: OllamaResponse =
+        serde_json::from_slice(line).context("Failed to parse Ollama stream line")?;
+
+    if let Some(error) = part.error {
+        return Err(anyhow::anyhow!("Ollama error: {}", error));
+    }
+    if let Some(message) = part.message {
+        on_text(&message.content);
+        reply.text.push_str(&message.content);
+    }
+    if part.done {
+        reply.model = part.model;
+        reply.usage = Usage {
+            input_tokens: part.prompt_eval_count,
+            output_tokens: part.eval_count,
+            ..Usage::default()
+        };
+        reply.stop_reason = part.done_reason;
+    }
+    Ok(())
+}
+
+/// Ollama `/api/chat` provider for local models
+pub struct OllamaProvider {
+    config: crate::genconfig::OllamaConfig,
+}
+
+impl OllamaProvider {
+    pub fn new(config: crate::genconfig::OllamaConfig) -> Self {
+        OllamaProvider { config }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn complete(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+    ) -> Result<Reply> {
+        check_supported(messages)?;
+        let request = build_request(&self.config, messages, max_tokens_override, false);
+        let http_request =
+            HttpRequest::post_json(chat_url(&self.config), build_headers(), &request)?;
+
+        let response = transport.send(&http_request).await?;
+
+        let ollama_response: OllamaResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse Ollama response")?;
+
+        ollama_response.into_reply()
+    }
+
+    /// Ollama streams newline-delimited JSON objects rather than SSE
+    async fn stream(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+        on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+    ) -> Result<Reply> {
+        check_supported(messages)?;
+        let request = build_request(&self.config, messages, max_tokens_override, true);
+        let http_request =
+            HttpRequest::post_json(chat_url(&self.config), build_headers(), &request)?;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut reply = Reply {
+            model: request.model.clone(),
+            ..Reply::default()
+        };
+
+        transport
+            .send_streaming(&http_request, &mut |chunk| {
+                buffer.extend_from_slice(chunk);
+
+                while let Some(end) = buffer.iter().position(|&byte| byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    apply_stream_line(&line, &mut reply, on_text)?;
+                }
+                Ok(())
+            })
+            .await?;
+        // The final object may arrive without a trailing newline
+        apply_stream_line(&buffer, &mut reply, on_text)?;
+
+        if reply.text.is_empty() {
+            Err(anyhow::anyhow!("No content in Ollama response"))
+        } else {
+            Ok(reply)
+        }
+    }
+
+    async fn count_tokens(&self, _transport: &dyn Transport, _messages: &[Message]) -> Result<u64> {
+        Err(anyhow::anyhow!("Token counting is not supported by Ollama"))
+    }
+
+    async fn list_models(&self, transport: &dyn Transport) -> Result<Vec<ModelInfo>> {
+        let http_request = HttpRequest::get(
+            format!("{}/api/tags", self.config.url.trim_end_matches('/')),
+            build_headers(),
+        );
+
+        let response = transport.send(&http_request).await?;
+
+        let tags: TagsResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse Ollama tags response")?;
+
+        Ok(tags
+            .models
+            .into_iter()
+            .map(|model| ModelInfo {
+                id: model.name,
+                display_name: None,
+                created: model.modified_at.as_deref().map(date_part),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::stub::{request_body, serve_once, transport};
+
+    fn provider(url: &str) -> OllamaProvider {
+        let config = toml::from_str(&format!(
+            r#"
+model = "llama-test"
+url = "{}/"
+system = "Be brief."
+"#,
+            url
+        ))
+        .unwrap();
+        OllamaProvider::new(config)
+    }
+
+    #[tokio::test]
+    async fn complete_sends_the_conversation_and_parses_the_reply() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![r#"{"model":"llama-test","message":{"role":"assistant","content":"Hello!"},"done":true,"done_reason":"stop","prompt_eval_count":12,"eval_count":3}"#],
+        )
+        .await;
+
+        let reply = provider(&url)
+            .complete(&transport(), &[Message::user("Hi")], Some(32))
+            .await
+            .unwrap();
+        assert_eq!(reply.text, "Hello!");
+        assert_eq!(reply.model, "llama-test");
+        assert_eq!(reply.stop_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (12, 3)
+        );
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /api/chat HTTP/1.1"));
+        let body = request_body(&request);
+        assert_eq!(body["model"], "llama-test");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["options"]["num_predict"], 32);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][0]["content"], "Be brief.");
+        assert_eq!(body["messages"][1]["role"], "user");
+        assert_eq!(body["messages"][1]["content"], "Hi");
+    }
+
+    #[tokio::test]
+    async fn complete_reports_an_error_body() {
+        let (url, _) = serve_once(
+            "200 OK",
+            vec![r#"{"error":"model 'llama-test' not found"}"#],
+        )
+        .await;
+
+        let error = provider(&url)
+            .complete(&transport(), &[Message::user("Hi")], None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Ollama error: model 'llama-test' not found"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_reads_lines_split_across_chunks_and_a_final_line_without_newline() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![
+                "{\"model\":\"llama-test\",\"message\":{\"content\":\"Hel\"},\"done\":false}\n{\"model\":\"llama-",
+                "test\",\"message\":{\"content\":\"lo\"},\"done\":false}\n\n",
+                "{\"model\":\"llama-test\",\"message\":{\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":12,\"eval_count\":2}",
+            ],
+        )
+        .await;
+
+        let mut deltas = Vec::new();
+        let reply = provider(&url)
+            .stream(&transport(), &[Message::user("Hi")], None, &mut |text| {
+                deltas.push(text.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(deltas, ["Hel", "lo", ""]);
+        assert_eq!(reply.text, "Hello");
+        assert_eq!(reply.model, "llama-test");
+        assert_eq!(reply.stop_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (12, 2)
+        );
+
+        let body = request_body(&request.await.unwrap());
+        assert_eq!(body["stream"], true);
+        assert!(body.get("options").is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_reports_an_error_line() {
+        let (url, _) = serve_once(
+            "200 OK",
+            vec!["{\"message\":{\"content\":\"Hi\"},\"done\":false}\n{\"error\":\"out of memory\"}\n"],
+        )
+        .await;
+
+        let error = provider(&url)
+            .stream(&transport(), &[Message::user("Hi")], None, &mut |_| {})
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("Ollama error: out of memory"));
+    }
+}
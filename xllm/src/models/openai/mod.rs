@@ -0,0 +1,561 @@
+use super::{ContentBlock, LlmProvider, Message, ModelInfo, Reply, Usage};
+use crate::transport::{HttpRequest, Transport};
+use crate::utils::date::format_unix_date;
+use crate::utils::sse::SseDecoder;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+pub struct OpenAIRequest {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    pub messages: Vec<OpenAIMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+/// A chat message in the OpenAI format. Text-only messages are sent as a
+/// plain string, messages with images as a list of content parts.
+#[derive(Serialize)]
+pub struct OpenAIMessage {
+    pub role: String,
+    pub content: OpenAIContent,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum OpenAIContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+    File { file: FileData },
+}
+
+#[derive(Serialize)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct FileData {
+    pub filename: String,
+    pub file_data: String,
+}
+
+impl From<&Message> for OpenAIMessage {
+    fn from(message: &Message) -> Self {
+        let text_only = !message.content.iter().any(|block| {
+            matches!(
+                block,
+                ContentBlock::Image { .. } | ContentBlock::Document { .. }
+            )
+        });
+        let content = if text_only {
+            OpenAIContent::Text(message.text())
+        } else {
+            OpenAIContent::Parts(
+                message
+                    .content
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => Some(ContentPart::Text { text: text.clone() }),
+                        ContentBlock::Image { source } => Some(ContentPart::ImageUrl {
+                            image_url: ImageUrl {
+                                url: source.data_url(),
+                            },
+                        }),
+                        ContentBlock::Document { source, title } => Some(ContentPart::File {
+                            file: FileData {
+                                filename: title.clone().unwrap_or_else(|| "document.pdf".to_string()),
+                                file_data: source.data_url(),
+                            },
+                        }),
+                        // Tool use is only supported with Claude
+                        ContentBlock::ToolUse { .. } | ContentBlock::ToolResult { .. } => None,
+                    })
+                    .collect(),
+            )
+        };
+
+        OpenAIMessage {
+            role: message.role.clone(),
+            content,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Deserialize)]
+pub struct OpenAIResponse {
+    #[serde(default)]
+    pub model: String,
+    pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+pub struct Choice {
+    pub message: ChoiceMessage,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChoiceMessage {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct OpenAIUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u64,
+}
+
+impl From<OpenAIUsage> for Usage {
+    fn from(usage: OpenAIUsage) -> Self {
+        // OpenAI counts cached tokens as part of the prompt; split them out
+        // to match the Anthropic shape
+        let cached = usage
+            .prompt_tokens_details
+            .map(|details| details.cached_tokens)
+            .unwrap_or_default();
+        Usage {
+            input_tokens: usage.prompt_tokens.saturating_sub(cached),
+            output_tokens: usage.completion_tokens,
+            cache_read_input_tokens: cached,
+            ..Usage::default()
+        }
+    }
+}
+
+impl OpenAIResponse {
+    pub fn into_reply(self) -> Result<Reply> {
+        // Extract text from the first choice
+        let choice = self
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No content in OpenAI response"))?;
+        let text = choice
+            .message
+            .content
+            .ok_or_else(|| anyhow::anyhow!("No content in OpenAI response"))?;
+
+        Ok(Reply {
+            text,
+            model: self.model,
+            usage: self.usage.map(Usage::from).unwrap_or_default(),
+            stop_reason: choice.finish_reason,
+            ..Reply::default()
+        })
+    }
+}
+
+/// A `chat.completion.chunk` sent when `"stream": true` is set
+#[derive(Deserialize)]
+pub struct StreamChunk {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    pub usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+pub struct ChunkChoice {
+    pub delta: ChunkDelta,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChunkDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ModelsResponse {
+    pub data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct ModelEntry {
+    pub id: String,
+    #[serde(default)]
+    pub created: Option<i64>,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+}
+
+pub fn build_request(
+    config: &crate::genconfig::OpenAIConfig,
+    messages: &[Message],
+    max_tokens_override: Option<u32>,
+    stream: bool,
+) -> OpenAIRequest {
+    let max_tokens = max_tokens_override.unwrap_or(config.max_tokens);
+    // The system prompt is sent as the first message
+    let system_message = config.system.clone().map(|system| OpenAIMessage {
+        role: "system".to_string(),
+        content: OpenAIContent::Text(system),
+    });
+
+    OpenAIRequest {
+        model: config.model.clone(),
+        max_completion_tokens: (!config.legacy_max_tokens).then_some(max_tokens),
+        max_tokens: config.legacy_max_tokens.then_some(max_tokens),
+        messages: system_message
+            .into_iter()
+            .chain(messages.iter().map(OpenAIMessage::from))
+            .collect(),
+        stream: stream.then_some(true),
+        stream_options: stream.then_some(StreamOptions {
+            include_usage: true,
+        }),
+    }
+}
+
+pub fn build_headers(config: &crate::genconfig::OpenAIConfig) -> HashMap<String, String> {
+    let mut headers = config.headers.clone();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+    // Local compatible servers often run without authentication
+    if !config.api_key.is_empty() {
+        headers.insert(
+            "Authorization".to_string(),
+            format!("Bearer {}", config.api_key),
+        );
+    }
+    headers
+}
+
+pub fn completions_url(config: &crate::genconfig::OpenAIConfig) -> String {
+    format!("{}/v1/chat/completions", config.url.trim_end_matches('/'))
+}
+
+pub fn models_url(config: &crate::genconfig::OpenAIConfig) -> String {
+    format!("{}/v1/models", config.url.trim_end_matches('/'))
+}
+
+/// OpenAI Chat Completions provider, also used for compatible servers
+pub struct OpenAIProvider {
+    config: crate::genconfig::OpenAIConfig,
+}
+
+impl OpenAIProvider {
+    pub fn new(config: crate::genconfig::OpenAIConfig) -> Self {
+        OpenAIProvider { config }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAIProvider {
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn complete(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+    ) -> Result<Reply> {
+        let request = build_request(&self.config, messages, max_tokens_override, false);
+        let http_request = HttpRequest::post_json(
+            completions_url(&self.config),
+            build_headers(&self.config),
+            &request,
+        )?;
+
+        let response = transport.send(&http_request).await?;
+
+        let openai_response: OpenAIResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse OpenAI API response")?;
+
+        openai_response.into_reply()
+    }
+
+    async fn stream(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+        on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+    ) -> Result<Reply> {
+        let request = build_request(&self.config, messages, max_tokens_override, true);
+        let http_request = HttpRequest::post_json(
+            completions_url(&self.config),
+            build_headers(&self.config),
+            &request,
+        )?;
+
+        let mut decoder = SseDecoder::default();
+        let mut reply = Reply {
+            model: request.model.clone(),
+            ..Reply::default()
+        };
+
+        transport
+            .send_streaming(&http_request, &mut |chunk| {
+                for data in decoder.push(chunk) {
+                    if data == "[DONE]" {
+                        continue;
+                    }
+
+                    let chunk: StreamChunk = serde_json::from_str(&data)
+                        .with_context(|| format!("Failed to parse stream chunk: {}", data))?;
+
+                    if let Some(model) = chunk.model {
+                        reply.model = model;
+                    }
+                    if let Some(usage) = chunk.usage {
+                        reply.usage = usage.into();
+                    }
+                    for choice in chunk.choices {
+                        if let Some(text) = choice.delta.content {
+                            on_text(&text);
+                            reply.text.push_str(&text);
+                        }
+                        if choice.finish_reason.is_some() {
+                            reply.stop_reason = choice.finish_reason;
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+        if reply.text.is_empty() {
+            Err(anyhow::anyhow!("No content in OpenAI response"))
+        } else {
+            Ok(reply)
+        }
+    }
+
+    async fn count_tokens(&self, _transport: &dyn Transport, _messages: &[Message]) -> Result<u64> {
+        Err(anyhow::anyhow!(
+            "Token counting is not supported by the OpenAI Chat Completions API"
+        ))
+    }
+
+    async fn list_models(&self, transport: &dyn Transport) -> Result<Vec<ModelInfo>> {
+        let http_request = HttpRequest::get(models_url(&self.config), build_headers(&self.config));
+
+        let response = transport.send(&http_request).await?;
+
+        let models: ModelsResponse = serde_json::from_slice(&response.body)
+            .context("Failed to parse OpenAI models response")?;
+
+        Ok(models
+            .data
+            .into_iter()
+            .map(|model| ModelInfo {
+                id: model.id,
+                display_name: model.owned_by.map(|owner| format!("owned by {}", owner)),
+                created: model.created.map(format_unix_date),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MediaSource;
+    use crate::transport::stub::{request_body, serve_once, transport};
+
+    fn provider(url: &str) -> OpenAIProvider {
+        let config = toml::from_str(&format!(
+            r#"
+model = "gpt-test"
+max_tokens = 64
+url = "{}/"
+api_key = "sk-test"
+system = "Be brief."
+"#,
+            url
+        ))
+        .unwrap();
+        OpenAIProvider::new(config)
+    }
+
+    #[tokio::test]
+    async fn complete_sends_the_conversation_and_parses_the_reply() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![r#"{"model":"gpt-test-1","choices":[{"message":{"role":"assistant","content":"Hello!"},"finish_reason":"stop"}],"usage":{"prompt_tokens":20,"completion_tokens":3,"prompt_tokens_details":{"cached_tokens":8}}}"#],
+        )
+        .await;
+
+        let reply = provider(&url)
+            .complete(&transport(), &[Message::user("Hi")], Some(32))
+            .await
+            .unwrap();
+        assert_eq!(reply.text, "Hello!");
+        assert_eq!(reply.model, "gpt-test-1");
+        assert_eq!(reply.stop_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            (
+                reply.usage.input_tokens,
+                reply.usage.cache_read_input_tokens,
+                reply.usage.output_tokens
+            ),
+            (12, 8, 3)
+        );
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1"));
+        assert!(request.contains("authorization: Bearer sk-test"));
+        let body = request_body(&request);
+        assert_eq!(body["model"], "gpt-test");
+        assert_eq!(body["max_completion_tokens"], 32);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("stream").is_none());
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][0]["content"], "Be brief.");
+        assert_eq!(body["messages"][1]["role"], "user");
+        assert_eq!(body["messages"][1]["content"], "Hi");
+    }
+
+    #[test]
+    fn images_and_documents_are_sent_as_content_parts() {
+        let message = Message::user_blocks(vec![
+            ContentBlock::text("Compare these"),
+            ContentBlock::Image {
+                source: MediaSource::base64("image/png", "aW1n".to_string()),
+            },
+            ContentBlock::Document {
+                source: MediaSource::base64("application/pdf", "cGRm".to_string()),
+                title: Some("report.pdf".to_string()),
+            },
+        ]);
+
+        let parts = serde_json::to_value(OpenAIMessage::from(&message)).unwrap();
+        assert_eq!(
+            parts["content"],
+            serde_json::json!([
+                {"type": "text", "text": "Compare these"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,aW1n"}},
+                {"type": "file", "file": {"filename": "report.pdf", "file_data": "data:application/pdf;base64,cGRm"}},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_reads_deltas_and_the_usage_only_final_chunk() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![
+                "data: {\"model\":\"gpt-test-1\",\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\ndata: {\"model\":\"gpt-test-1\",\"choices\":[{\"delta\":{\"content\":\"Hel",
+                "lo\"}}]}\n\ndata: {\"model\":\"gpt-test-1\",\"choices\":[{\"delta\":{\"content\":\" there\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: {\"model\":\"gpt-test-1\",\"choices\":[],\"usage\":{\"prompt_tokens\":20,\"completion_tokens\":4}}\n\ndata: [DONE]\n\n",
+            ],
+        )
+        .await;
+
+        let mut deltas = Vec::new();
+        let reply = provider(&url)
+            .stream(&transport(), &[Message::user("Hi")], None, &mut |text| {
+                deltas.push(text.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(deltas, ["", "Hello", " there"]);
+        assert_eq!(reply.text, "Hello there");
+        assert_eq!(reply.model, "gpt-test-1");
+        assert_eq!(reply.stop_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (20, 4)
+        );
+
+        let body = request_body(&request.await.unwrap());
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert_eq!(body["max_completion_tokens"], 64);
+    }
+
+    fn custom_provider(url: &str, extra: &str) -> OpenAIProvider {
+        let config: crate::genconfig::CustomConfig = toml::from_str(&format!(
+            r#"
+model = "local-test"
+url = "{}"
+max_tokens = 48
+{}
+"#,
+            url, extra
+        ))
+        .unwrap();
+        OpenAIProvider::new(config.to_openai_config())
+    }
+
+    #[tokio::test]
+    async fn custom_endpoints_send_headers_and_legacy_max_tokens() {
+        let (url, request) = serve_once(
+            "200 OK",
+            vec![r#"{"choices":[{"message":{"content":"Hi"}}]}"#],
+        )
+        .await;
+
+        custom_provider(
+            &url,
+            "api_key = \"local-key\"\n[headers]\nx-team = \"research\"",
+        )
+        .complete(&transport(), &[Message::user("Hi")], None)
+        .await
+        .unwrap();
+
+        let request = request.await.unwrap();
+        assert!(request.contains("x-team: research"));
+        assert!(request.contains("authorization: Bearer local-key"));
+        let body = request_body(&request);
+        assert_eq!(body["max_tokens"], 48);
+        assert!(body.get("max_completion_tokens").is_none());
+    }
+
+    #[test]
+    fn an_empty_api_key_leaves_out_authorization() {
+        for extra in ["", "api_key = \"\""] {
+            let without_key = custom_provider("http://localhost:8080", extra);
+            let headers = build_headers(&without_key.config);
+            assert!(!headers.contains_key("Authorization"), "{:?}", extra);
+            assert_eq!(headers["Content-Type"], "application/json");
+        }
+
+        let with_key = custom_provider("http://localhost:8080", "api_key = \"local-key\"");
+        assert_eq!(
+            build_headers(&with_key.config)["Authorization"],
+            "Bearer local-key"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! A provider that replays canned replies, for tests of the layers above providers
+
+use super::{LlmProvider, Message, ModelInfo, Reply};
+use crate::transport::Transport;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Replays scripted replies in order and records the conversations it was sent
+pub struct Scripted {
+    replies: Mutex<VecDeque<Reply>>,
+    pub seen: Mutex<Vec<Vec<Message>>>,
+}
+
+impl Scripted {
+    pub fn new(replies: impl IntoIterator<Item = Reply>) -> Self {
+        Scripted {
+            replies: Mutex::new(replies.into_iter().collect()),
+            seen: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for Scripted {
+    fn model(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(
+        &self,
+        _transport: &dyn Transport,
+        messages: &[Message],
+        _max_tokens_override: Option<u32>,
+    ) -> Result<Reply> {
+        self.seen.lock().unwrap().push(messages.to_vec());
+        Ok(self.replies.lock().unwrap().pop_front().unwrap())
+    }
+
+    async fn stream(
+        &self,
+        transport: &dyn Transport,
+        messages: &[Message],
+        max_tokens_override: Option<u32>,
+        _on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+    ) -> Result<Reply> {
+        self.complete(transport, messages, max_tokens_override)
+            .await
+    }
+
+    async fn count_tokens(&self, _transport: &dyn Transport, _messages: &[Message]) -> Result<u64> {
+        Ok(0)
+    }
+
+    async fn list_models(&self, _transport: &dyn Transport) -> Result<Vec<ModelInfo>> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::genconfig::ToolConfig;
+use crate::models::{LlmProvider, Message, Reply, Usage};
+use crate::tools::send_with_tools;
+use crate::transport::Transport;
+use anyhow::{Context, Result};
+use jsonschema::Validator;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Attempts before giving up on a reply that does not match the schema
+const MAX_SCHEMA_ATTEMPTS: usize = 3;
+
+/// A JSON schema from `--json-schema` that replies must satisfy
+pub struct JsonSchema {
+    schema: Value,
+    validator: Validator,
+}
+
+impl JsonSchema {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read JSON schema: {}", path))?;
+        let schema: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse JSON schema: {}", path))?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON schema {}: {}", path, e))?;
+
+        Ok(JsonSchema { schema, validator })
+    }
+
+    /// System prompt instruction asking for JSON that matches the schema
+    pub fn instruction(&self) -> String {
+        format!(
+            "Respond with a single JSON value that conforms to the following JSON schema. \
+             Output only the JSON, with no explanation and no code fences.\n\n{}",
+            serde_json::to_string_pretty(&self.schema).unwrap_or_default()
+        )
+    }
+
+    /// Parse a reply as JSON and check it against the schema. The error
+    /// describes what is wrong so it can be sent back to the model.
+    pub fn validate(&self, text: &str) -> Result<Value, String> {
+        let value: Value = serde_json::from_str(strip_code_fence(text))
+            .map_err(|e| format!("The reply is not valid JSON: {}", e))?;
+
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .map(|error| {
+                let path = error.instance_path().to_string();
+                if path.is_empty() {
+                    error.to_string()
+                } else {
+                    format!("at {}: {}", path, error)
+                }
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(format!(
+                "The reply does not match the schema:\n- {}",
+                errors.join("\n- ")
+            ))
+        }
+    }
+}
+
+/// Send a conversation until the reply validates against `schema`, feeding
+/// validation errors back to the model. Every attempt is kept in `messages`
+/// except the final reply, which the caller records.
+pub async fn send_with_schema(
+    provider: &dyn LlmProvider,
+    transport: &dyn Transport,
+    tools: &BTreeMap<String, ToolConfig>,
+    schema: &JsonSchema,
+    messages: &mut Vec<Message>,
+    max_tokens_override: Option<u32>,
+    on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+) -> Result<(Reply, Value)> {
+    let mut usage = Usage::default();
+    let mut attempt = 1;
+
+    loop {
+        // Replies are validated as a whole, so they are never streamed
+        let mut reply = send_with_tools(
+            provider,
+            transport,
+            tools,
+            messages,
+            max_tokens_override,
+            false,
+            on_text,
+        )
+        .await?;
+        usage += reply.usage;
+
+        match schema.validate(&reply.text) {
+            Ok(value) => {
+                reply.usage = usage;
+                return Ok((reply, value));
+            }
+            Err(problem) if attempt >= MAX_SCHEMA_ATTEMPTS => {
+                return Err(anyhow::anyhow!(
+                    "No valid JSON after {} attempts. {}",
+                    MAX_SCHEMA_ATTEMPTS,
+                    problem
+                ));
+            }
+            Err(problem) => {
+                eprintln!(
+                    "⚠️  Reply failed schema validation, retrying ({}/{})",
+                    attempt, MAX_SCHEMA_ATTEMPTS
+                );
+                messages.push(reply.to_message());
+                messages.push(Message::user(format!(
+                    "{}\n\nReply again with only the corrected JSON.",
+                    problem
+                )));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Models sometimes wrap JSON in a markdown code fence despite instructions
+fn strip_code_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+    trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scripted::Scripted;
+    use crate::transport::stub::transport;
+
+    fn schema() -> JsonSchema {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "answer": { "type": "integer" } },
+            "required": ["answer"]
+        });
+        JsonSchema {
+            validator: jsonschema::validator_for(&schema).unwrap(),
+            schema,
+        }
+    }
+
+    fn reply(text: &str) -> Reply {
+        Reply {
+            text: text.to_string(),
+            usage: Usage {
+                input_tokens: 10,
+                output_tokens: 4,
+                ..Usage::default()
+            },
+            stop_reason: Some("end_turn".to_string()),
+            ..Reply::default()
+        }
+    }
+
+    #[test]
+    fn strip_code_fence_unwraps_fenced_json() {
+        assert_eq!(strip_code_fence("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+        assert_eq!(strip_code_fence("  ```\n[1, 2]\n```\n"), "[1, 2]");
+        assert_eq!(strip_code_fence(" {\"a\": 1} \n"), "{\"a\": 1}");
+        // Without a closing fence the text is left alone
+        assert_eq!(
+            strip_code_fence("```json\n{\"a\": 1}"),
+            "```json\n{\"a\": 1}"
+        );
+    }
+
+    #[test]
+    fn validate_explains_what_is_wrong() {
+        let schema = schema();
+        assert_eq!(
+            schema.validate("```json\n{\"answer\": 42}\n```").unwrap(),
+            serde_json::json!({ "answer": 42 })
+        );
+        assert!(
+            schema
+                .validate("forty-two")
+                .unwrap_err()
+                .starts_with("The reply is not valid JSON")
+        );
+        let problem = schema.validate("{\"answer\": \"42\"}").unwrap_err();
+        assert!(problem.contains("at /answer:"), "{}", problem);
+    }
+
+    #[tokio::test]
+    async fn an_invalid_reply_is_retried_with_the_problem() {
+        let provider = Scripted::new([reply("{\"answer\": \"42\"}"), reply("{\"answer\": 42}")]);
+        let mut messages = vec![Message::user("What is the answer?")];
+
+        let (reply, value) = send_with_schema(
+            &provider,
+            &transport(),
+            &BTreeMap::new(),
+            &schema(),
+            &mut messages,
+            None,
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, serde_json::json!({ "answer": 42 }));
+        assert_eq!(reply.text, "{\"answer\": 42}");
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (20, 8)
+        );
+        // The failed reply and the correction request stay in the conversation
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].text(), "{\"answer\": \"42\"}");
+        assert!(
+            messages[2]
+                .text()
+                .starts_with("The reply does not match the schema")
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_maximum_number_of_attempts() {
+        let provider = Scripted::new((0..MAX_SCHEMA_ATTEMPTS).map(|_| reply("not json")));
+        let mut messages = vec![Message::user("What is the answer?")];
+
+        let error = send_with_schema(
+            &provider,
+            &transport(),
+            &BTreeMap::new(),
+            &schema(),
+            &mut messages,
+            None,
+            &mut |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("No valid JSON after 3 attempts")
+        );
+        assert_eq!(provider.seen.lock().unwrap().len(), MAX_SCHEMA_ATTEMPTS);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::models::{Message, Reply, Usage};
+use crate::utils::date::unix_now;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A saved conversation, stored as JSON under the XDG data directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub model: String,
+    /// Unix timestamp (seconds) of the first exchange
+    pub created_at: u64,
+    /// Unix timestamp (seconds) of the latest exchange
+    pub updated_at: u64,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let now = unix_now();
+        Session {
+            id: format!("{:08x}", rand::random::<u32>()),
+            model: String::new(),
+            created_at: now,
+            updated_at: now,
+            messages: Vec::new(),
+            usage: Usage::default(),
+        }
+    }
+
+    /// Load a session by id
+    pub fn load(id: &str) -> Result<Self> {
+        let path = session_path(&get_sessions_dir()?, id)?;
+        if !path.exists() {
+            return Err(anyhow::anyhow!(
+                "Session '{}' not found. Run `xllm sessions` to list saved sessions.",
+                id
+            ));
+        }
+        read_session(&path)
+    }
+
+    /// Load the most recently updated session
+    pub fn load_latest() -> Result<Self> {
+        list_sessions()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No saved sessions to continue"))
+    }
+
+    /// Append the assistant reply to the history and accumulate its usage
+    pub fn record(&mut self, reply: &Reply) {
+        self.messages.push(reply.to_message());
+        self.model = reply.model.clone();
+        self.usage += reply.usage;
+        self.updated_at = unix_now();
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let sessions_dir = get_sessions_dir()?;
+        fs::create_dir_all(&sessions_dir).with_context(|| {
+            format!(
+                "Failed to create sessions directory: {}",
+                sessions_dir.display()
+            )
+        })?;
+
+        let path = session_path(&sessions_dir, &self.id)?;
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize session")?;
+        fs::write(&path, content)
+            .with_context(|| format!("Failed to write session file: {}", path.display()))
+    }
+
+    /// First line of the opening prompt, for listings
+    pub fn title(&self) -> String {
+        let text = self
+            .messages
+            .first()
+            .map(|message| message.text())
+            .unwrap_or_default();
+        let first = text.lines().next().unwrap_or("");
+        let mut title: String = first.chars().take(60).collect();
+        if first.chars().count() > 60 {
+            title.push('…');
+        }
+        title
+    }
+}
+
+/// Sessions directory (~/.local/share/xllm/sessions)
+pub fn get_sessions_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|p| p.join("xllm").join("sessions"))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine data directory"))
+}
+
+/// All saved sessions, most recently updated first
+pub fn list_sessions() -> Result<Vec<Session>> {
+    let sessions_dir = get_sessions_dir()?;
+    if !sessions_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut sessions = Vec::new();
+    for entry in fs::read_dir(&sessions_dir)
+        .with_context(|| format!("Failed to read sessions directory: {}", sessions_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            match read_session(&path) {
+                Ok(session) => sessions.push(session),
+                Err(e) => eprintln!("⚠️  Skipping {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+    Ok(sessions)
+}
+
+/// Path of a session file. Ids are the 8 hex digits `Session::new` generates;
+/// anything else is rejected so an id can't point outside the directory.
+fn session_path(sessions_dir: &Path, id: &str) -> Result<PathBuf> {
+    let valid = id.len() == 8 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid session id '{}'. Run `xllm sessions` to list saved sessions.",
+            id
+        ));
+    }
+    Ok(sessions_dir.join(format!("{}.json", id)))
+}
+
+/// List saved sessions on stdout, newest first. A closed pipe, as with
+/// `xllm sessions | head`, ends the listing quietly.
+pub fn print_sessions() -> Result<()> {
+    let sessions = list_sessions()?;
+    let mut stdout = std::io::stdout().lock();
+    let result = sessions.iter().try_for_each(|session| {
+        writeln!(
+            stdout,
+            "{}  {:>9}  {:<28}  {:>3} msgs  {}",
+            session.id,
+            format_age(session.updated_at),
+            session.model,
+            session.messages.len(),
+            session.title()
+        )
+    });
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.context("Failed to print sessions"),
+    }
+}
+
+fn read_session(path: &Path) -> Result<Session> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read session file: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse session file: {}", path.display()))
+}
+
+/// Human readable age of a unix timestamp, e.g. "5m ago"
+pub fn format_age(timestamp: u64) -> String {
+    let elapsed = unix_now().saturating_sub(timestamp);
+    match elapsed {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", elapsed / 60),
+        3600..86400 => format!("{}h ago", elapsed / 3600),
+        _ => format!("{}d ago", elapsed / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_path_accepts_generated_ids() {
+        let dir = Path::new("/data/sessions");
+        let id = Session::new().id;
+        assert_eq!(
+            session_path(dir, &id).unwrap(),
+            dir.join(format!("{}.json", id))
+        );
+    }
+
+    #[test]
+    fn session_path_rejects_traversal_and_other_ids() {
+        let dir = Path::new("/data/sessions");
+        for id in [
+            "../../x",
+            "../1234abcd",
+            "/etc/passwd",
+            "1234ABCD",
+            "1234abc",
+            "1234abcde",
+            "",
+        ] {
+            assert!(session_path(dir, id).is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn load_rejects_a_traversal_id() {
+        let error = Session::load("../../x").unwrap_err();
+        assert!(error.to_string().starts_with("Invalid session id"));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::genconfig::{Config, TemplateConfig, get_templates_dir, resolve_template_variables};
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+
+/// Look up a template by name. `[templates.<name>]` in config.toml takes
+/// precedence over `<name>.toml` in the templates directory.
+pub fn load_template(config: &Config, name: &str) -> Result<TemplateConfig> {
+    if let Some(template) = config.templates.get(name) {
+        return Ok(template.clone());
+    }
+
+    let path = get_templates_dir()?.join(format!("{}.toml", name));
+    if path.exists() {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read template: {}", path.display()))?;
+        return toml::from_str(&content)
+            .with_context(|| format!("Failed to parse template: {}", path.display()));
+    }
+
+    let available = template_names(config);
+    Err(anyhow::anyhow!(
+        "Unknown template: {}. Available templates: {}",
+        name,
+        if available.is_empty() {
+            "none (add a [templates.<name>] section to config.toml)".to_string()
+        } else {
+            available.into_iter().collect::<Vec<_>>().join(", ")
+        }
+    ))
+}
+
+/// Names of all templates from config.toml and the templates directory
+pub fn template_names(config: &Config) -> BTreeSet<String> {
+    let mut names: BTreeSet<String> = config.templates.keys().cloned().collect();
+
+    if let Ok(entries) = get_templates_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "toml")
+                && let Some(stem) = path.file_stem()
+            {
+                names.insert(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    names
+}
+
+/// Fill the template's prompt and system prompt with `--var` values
+pub fn render_template(
+    template: &TemplateConfig,
+    vars: &HashMap<String, String>,
+) -> Result<TemplateConfig> {
+    Ok(TemplateConfig {
+        prompt: resolve_template_variables(&template.prompt, vars)?,
+        system: template
+            .system
+            .as_deref()
+            .map(|system| resolve_template_variables(system, vars))
+            .transpose()?,
+        ..template.clone()
+    })
+}
+
+/// Parse a `--var NAME=VALUE` argument
+pub fn parse_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got `{}`", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_var_splits_at_the_first_equals() {
+        assert_eq!(
+            parse_var(" lang =rust=fast").unwrap(),
+            ("lang".to_string(), "rust=fast".to_string())
+        );
+        assert_eq!(
+            parse_var("empty=").unwrap(),
+            ("empty".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn parse_var_needs_a_name_and_equals() {
+        assert!(parse_var("lang").is_err());
+        assert!(parse_var(" =rust").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::genconfig::{Config, ToolConfig, resolve_template_variables};
+use crate::models::claude::ToolDefinition;
+use crate::models::{ContentBlock, LlmProvider, Message, Reply, ToolCall, Usage, send_messages};
+use crate::transport::Transport;
+use anyhow::{Context, Result};
+use rustyline::DefaultEditor;
+use rustyline::config::Behavior;
+use rustyline::error::ReadlineError;
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Upper bound on tool round trips for a single prompt
+const MAX_TOOL_ROUNDS: usize = 20;
+
+/// Tool output beyond this is truncated before it is sent back
+const MAX_TOOL_OUTPUT_BYTES: usize = 100_000;
+
+/// Returned when Ctrl-C is pressed at a tool confirmation. The prompt reads
+/// the terminal in raw mode, so the keypress never becomes a signal.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Definitions for every tool in `[tools]`, as sent to the API
+pub fn tool_definitions(config: &Config) -> Result<Vec<ToolDefinition>> {
+    if config.tools.is_empty() {
+        return Err(anyhow::anyhow!(
+            "--tools was given but no tools are configured. Add a [tools.<name>] section to config.toml"
+        ));
+    }
+
+    Ok(config
+        .tools
+        .iter()
+        .map(|(name, tool)| ToolDefinition {
+            name: name.clone(),
+            description: tool.description.clone(),
+            input_schema: tool.input_schema.clone(),
+        })
+        .collect())
+}
+
+/// Send a conversation and keep answering `tool_use` requests with local
+/// command output until the model ends its turn. Tool calls and results are
+/// appended to `messages`; the final reply is returned with usage summed over
+/// every round. `on_text` is called before tools run, with empty text if
+/// necessary, so callers can clear progress output first. Dropping the future
+/// kills a running tool; Ctrl-C at a confirmation returns [`Interrupted`].
+pub async fn send_with_tools(
+    provider: &dyn LlmProvider,
+    transport: &dyn Transport,
+    tools: &BTreeMap<String, ToolConfig>,
+    messages: &mut Vec<Message>,
+    max_tokens_override: Option<u32>,
+    stream: bool,
+    on_text: &mut (dyn for<'a> FnMut(&'a str) + Send),
+) -> Result<Reply> {
+    let mut usage = Usage::default();
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let mut reply = send_messages(
+            provider,
+            transport,
+            messages,
+            max_tokens_override,
+            stream,
+            on_text,
+        )
+        .await?;
+        usage += reply.usage;
+
+        if reply.tool_calls.is_empty() || reply.stop_reason.as_deref() != Some("tool_use") {
+            reply.usage = usage;
+            return Ok(reply);
+        }
+
+        // Show what the model said before asking for tools
+        if stream {
+            on_text("\n");
+        } else if reply.text.is_empty() {
+            on_text("");
+        } else {
+            on_text(&format!("{}\n", reply.text));
+        }
+
+        messages.push(reply.to_message());
+        let mut results = Vec::with_capacity(reply.tool_calls.len());
+        for call in &reply.tool_calls {
+            results.push(run_tool(tools, call, confirm).await?);
+        }
+        messages.push(Message::user_blocks(results));
+    }
+
+    Err(anyhow::anyhow!(
+        "Stopped after {} rounds of tool calls without a final answer",
+        MAX_TOOL_ROUNDS
+    ))
+}
+
+/// Run one tool call and turn its output into a `tool_result` block. Only an
+/// interrupted confirmation is an error; tool failures go back to the model.
+/// `ask` answers the confirmation question for tools that need one.
+async fn run_tool(
+    tools: &BTreeMap<String, ToolConfig>,
+    call: &ToolCall,
+    ask: fn(&str) -> Result<bool>,
+) -> Result<ContentBlock> {
+    let (content, is_error) = match execute_tool(tools, call, ask).await {
+        Ok(result) => result,
+        Err(e) if e.is::<Interrupted>() => return Err(e),
+        Err(e) => {
+            eprintln!("❌ Tool {} failed: {}", call.name, e);
+            (format!("Error: {:#}", e), true)
+        }
+    };
+
+    Ok(ContentBlock::ToolResult {
+        tool_use_id: call.id.clone(),
+        content,
+        is_error,
+    })
+}
+
+/// Returns the command output and whether it should be reported as an error
+async fn execute_tool(
+    tools: &BTreeMap<String, ToolConfig>,
+    call: &ToolCall,
+    ask: fn(&str) -> Result<bool>,
+) -> Result<(String, bool)> {
+    let tool = tools
+        .get(&call.name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown tool: {}", call.name))?;
+
+    // Tool input values fill the `{{field}}` placeholders in the command
+    let vars: HashMap<String, String> = call
+        .input
+        .as_object()
+        .map(|object| {
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let args = tool
+        .command
+        .iter()
+        .map(|arg| resolve_template_variables(arg, &vars))
+        .collect::<Result<Vec<_>>>()?;
+    let (program, program_args) = args
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Tool {} has an empty command", call.name))?;
+
+    let command_line = args.join(" ");
+    if tool.confirm {
+        let question = format!("🔧 Run {}: {}?", call.name, command_line);
+        let approved = tokio::task::spawn_blocking(move || ask(&question))
+            .await
+            .context("Tool confirmation failed")??;
+        if !approved {
+            return Ok(("The user declined to run this tool.".to_string(), true));
+        }
+    }
+    eprintln!("🔧 Running {}: {}", call.name, command_line);
+
+    let mut child = Command::new(program)
+        .args(program_args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to start {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read its input; a closed pipe is not an error
+        let _ = stdin.write_all(call.input.to_string().as_bytes()).await;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .with_context(|| format!("Failed to run {}", program))?;
+
+    let mut content = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.stderr.is_empty() {
+        content.push_str("\nstderr:\n");
+        content.push_str(&String::from_utf8_lossy(&output.stderr));
+    }
+    if !output.status.success() {
+        content.push_str(&format!("\n{}", output.status));
+    }
+    if content.len() > MAX_TOOL_OUTPUT_BYTES {
+        let mut end = MAX_TOOL_OUTPUT_BYTES;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        content.truncate(end);
+        content.push_str("\n[output truncated]");
+    }
+
+    Ok((content, !output.status.success()))
+}
+
+/// Ask a yes/no question on the terminal. Piped stdin has already been read
+/// for the prompt, so the question goes through /dev/tty. Without a terminal
+/// the answer is no.
+fn confirm(question: &str) -> Result<bool> {
+    let question = format!("{} [y/N] ", question);
+    if !std::io::stdin().is_terminal() && std::fs::File::open("/dev/tty").is_err() {
+        eprintln!("{}", question);
+        return Ok(false);
+    }
+
+    let config = rustyline::Config::builder()
+        .behavior(Behavior::PreferTerm)
+        .auto_add_history(false)
+        .build();
+    let mut editor = DefaultEditor::with_config(config).context("Failed to open the terminal")?;
+    match editor.readline(&question) {
+        Ok(answer) => Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")),
+        Err(ReadlineError::Interrupted) => Err(Interrupted.into()),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scripted::Scripted;
+    use crate::transport::stub::transport;
+
+    fn tools(command: &[&str], confirm: bool) -> BTreeMap<String, ToolConfig> {
+        let tool = ToolConfig {
+            description: "Greets someone".to_string(),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            input_schema: serde_json::json!({ "type": "object" }),
+            confirm,
+        };
+        BTreeMap::from([("greet".to_string(), tool)])
+    }
+
+    fn call(input: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: "toolu_1".to_string(),
+            name: "greet".to_string(),
+            input,
+        }
+    }
+
+    fn tool_use(input: serde_json::Value) -> Reply {
+        Reply {
+            text: "Let me check.".to_string(),
+            usage: Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+                ..Usage::default()
+            },
+            stop_reason: Some("tool_use".to_string()),
+            tool_calls: vec![call(input)],
+            ..Reply::default()
+        }
+    }
+
+    fn declined(_question: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn tool_result(block: &ContentBlock) -> (&str, &str, bool) {
+        match block {
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } => (tool_use_id, content, *is_error),
+            other => panic!("expected a tool_result, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_use_is_answered_with_a_tool_result() {
+        let provider = Scripted::new([
+            tool_use(serde_json::json!({ "name": "world" })),
+            Reply {
+                text: "Done.".to_string(),
+                usage: Usage {
+                    input_tokens: 20,
+                    output_tokens: 2,
+                    ..Usage::default()
+                },
+                stop_reason: Some("end_turn".to_string()),
+                ..Reply::default()
+            },
+        ]);
+        let mut messages = vec![Message::user("Greet the world")];
+        let mut shown = Vec::new();
+
+        let reply = send_with_tools(
+            &provider,
+            &transport(),
+            &tools(&["echo", "hello {{name}}"], false),
+            &mut messages,
+            None,
+            false,
+            &mut |text| shown.push(text.to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(reply.text, "Done.");
+        assert_eq!(
+            (reply.usage.input_tokens, reply.usage.output_tokens),
+            (30, 7)
+        );
+        assert_eq!(shown, ["Let me check.\n"]);
+
+        // The second request carries the tool call and its result
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(
+            &messages[1].content[1],
+            ContentBlock::ToolUse { id, name, .. } if id == "toolu_1" && name == "greet"
+        ));
+        assert_eq!(messages[2].role, "user");
+        assert_eq!(
+            tool_result(&messages[2].content[0]),
+            ("toolu_1", "hello world\n", false)
+        );
+        assert_eq!(provider.seen.lock().unwrap()[1].len(), 3);
+    }
+
+    #[tokio::test]
+    async fn placeholders_are_filled_from_the_tool_input() {
+        let tools = tools(&["echo", "{{name}}", "{{ count }}"], false);
+
+        let (output, is_error) = execute_tool(
+            &tools,
+            &call(serde_json::json!({ "name": "a b", "count": 3 })),
+            declined,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output, "a b 3\n");
+        assert!(!is_error);
+
+        let result = run_tool(&tools, &call(serde_json::json!({ "name": "a" })), declined)
+            .await
+            .unwrap();
+        let (_, content, is_error) = tool_result(&result);
+        assert!(
+            content.contains("Missing template variables: count"),
+            "{}",
+            content
+        );
+        assert!(is_error);
+    }
+
+    #[tokio::test]
+    async fn a_declined_confirmation_is_reported_as_an_error() {
+        let result = run_tool(
+            &tools(&["echo", "hello"], true),
+            &call(serde_json::json!({})),
+            declined,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            tool_result(&result),
+            ("toolu_1", "The user declined to run this tool.", true)
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_after_the_maximum_number_of_rounds() {
+        let provider = Scripted::new(
+            (0..MAX_TOOL_ROUNDS).map(|_| tool_use(serde_json::json!({ "name": "again" }))),
+        );
+        let mut messages = vec![Message::user("Loop")];
+
+        let error = send_with_tools(
+            &provider,
+            &transport(),
+            &tools(&["echo", "{{name}}"], false),
+            &mut messages,
+            None,
+            false,
+            &mut |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("Stopped after 20 rounds"));
+        assert_eq!(provider.seen.lock().unwrap().len(), MAX_TOOL_ROUNDS);
+        assert_eq!(messages.len(), 1 + 2 * MAX_TOOL_ROUNDS);
+    }
+}
@@ -0,0 +1,320 @@
+#[cfg(test)]
+pub mod stub;
+
+use crate::cache::{CacheMode, CachingTransport, ResponseCache};
+use crate::genconfig::{Config, ModelProvider};
+use crate::utils::proxy::{TcpProxyTransport, get_proxy_url};
+use crate::utils::retry::{RetryPolicy, RetryTransport};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+/// How long a request may take to connect and to complete
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub total: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            total: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Timeouts for a model: its own settings, then `[global]`, then the defaults
+    pub fn for_model(config: &Config, model: &ModelProvider) -> Self {
+        let overrides = model.timeouts();
+        let (connect, total) = match &config.global {
+            Some(global) => (global.connect_timeout_secs, global.timeout_secs),
+            None => (DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_TIMEOUT_SECS),
+        };
+        Timeouts {
+            connect: Duration::from_secs(overrides.connect_timeout_secs.unwrap_or(connect)),
+            total: Duration::from_secs(overrides.timeout_secs.unwrap_or(total)),
+        }
+    }
+}
+
+/// A provider-agnostic HTTP request. This is also the payload the TCP proxy
+/// encrypts, so its shape must match `xllm-proxy`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn get(url: String, headers: HashMap<String, String>) -> Self {
+        HttpRequest {
+            method: "GET".to_string(),
+            url,
+            headers,
+            body: Vec::new(),
+        }
+    }
+
+    /// POST request with a JSON-serialized body
+    pub fn post_json<T: Serialize>(
+        url: String,
+        headers: HashMap<String, String>,
+        body: &T,
+    ) -> Result<Self> {
+        Ok(HttpRequest {
+            method: "POST".to_string(),
+            url,
+            headers,
+            body: serde_json::to_vec(body).context("Failed to serialize request body")?,
+        })
+    }
+}
+
+impl HttpResponse {
+    /// Turn non-2xx responses into errors carrying the status and body
+    pub fn error_for_status(self) -> Result<Self> {
+        if (200..300).contains(&self.status_code) {
+            Ok(self)
+        } else {
+            Err(StatusError {
+                status_code: self.status_code,
+                headers: self.headers,
+                body: String::from_utf8_lossy(&self.body).to_string(),
+            }
+            .into())
+        }
+    }
+}
+
+/// A non-2xx response, kept whole so callers can inspect the status and headers
+#[derive(Debug)]
+pub struct StatusError {
+    pub status_code: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl StatusError {
+    /// Case-insensitive header lookup
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "API request failed with status {}: {}",
+            self.status_code, self.body
+        )
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// How requests reach a provider: directly over HTTP or through the encrypted TCP proxy
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send a request and return the full response. Non-2xx responses are errors.
+    async fn send(&self, request: &HttpRequest) -> Result<HttpResponse>;
+
+    /// Send a request and hand the response body to `on_chunk` as it arrives.
+    /// Transports that cannot stream deliver the whole body as a single chunk.
+    async fn send_streaming(
+        &self,
+        request: &HttpRequest,
+        on_chunk: &mut (dyn for<'a> FnMut(&'a [u8]) -> Result<()> + Send),
+    ) -> Result<()> {
+        let response = self.send(request).await?;
+        on_chunk(&response.body)
+    }
+
+    /// Whether `send_streaming` delivers the body incrementally
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Whether the last response came from the local response cache
+    fn served_from_cache(&self) -> bool {
+        false
+    }
+}
+
+/// Sends requests straight to the provider with reqwest
+pub struct DirectTransport {
+    client: Client,
+    timeouts: Timeouts,
+}
+
+impl DirectTransport {
+    pub fn new(timeouts: Timeouts) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.total)
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(DirectTransport { client, timeouts })
+    }
+
+    /// Name the limit that was hit when reqwest reports a timeout
+    fn timeout_context(&self, error: reqwest::Error, url: &str) -> anyhow::Error {
+        let message = if error.is_connect() {
+            format!(
+                "Timed out connecting to {} after {}s",
+                url,
+                self.timeouts.connect.as_secs()
+            )
+        } else if error.is_timeout() {
+            format!(
+                "Request to {} timed out after {}s",
+                url,
+                self.timeouts.total.as_secs()
+            )
+        } else {
+            format!("Failed to send request to {}", url)
+        };
+        anyhow::Error::new(error).context(message)
+    }
+
+    async fn execute(&self, request: &HttpRequest) -> Result<reqwest::Response> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())
+            .with_context(|| format!("Unsupported HTTP method: {}", request.method))?;
+
+        let mut builder = self
+            .client
+            .request(method, &request.url)
+            .headers(to_header_map(&request.headers)?);
+        if !request.body.is_empty() {
+            builder = builder.body(request.body.clone());
+        }
+
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| self.timeout_context(e, &request.url))?;
+
+        if !response.status().is_success() {
+            let status_code = response.status().as_u16();
+            let headers = header_strings(response.headers());
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError {
+                status_code,
+                headers,
+                body,
+            }
+            .into());
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Transport for DirectTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let response = self.execute(request).await?;
+        let status_code = response.status().as_u16();
+        let headers = header_strings(response.headers());
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| self.timeout_context(e, &request.url))
+            .context("Failed to read response body")?
+            .to_vec();
+
+        Ok(HttpResponse {
+            status_code,
+            headers,
+            body,
+        })
+    }
+
+    async fn send_streaming(
+        &self,
+        request: &HttpRequest,
+        on_chunk: &mut (dyn for<'a> FnMut(&'a [u8]) -> Result<()> + Send),
+    ) -> Result<()> {
+        let response = self.execute(request).await?;
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk
+                .map_err(|e| self.timeout_context(e, &request.url))
+                .context("Failed to read response stream")?;
+            on_chunk(&chunk)?;
+        }
+        Ok(())
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+}
+
+/// Pick the transport configured in `[global]`, wrapped in the retry policy
+/// and, when `[cache]` is enabled, the response cache
+pub fn transport_for(
+    config: &Config,
+    timeouts: Timeouts,
+    cache_mode: CacheMode,
+) -> Result<Box<dyn Transport>> {
+    let inner: Box<dyn Transport> = match get_proxy_url(config)? {
+        Some(proxy_url) => Box::new(TcpProxyTransport::new(proxy_url, timeouts)),
+        None => Box::new(DirectTransport::new(timeouts)?),
+    };
+    let transport: Box<dyn Transport> = Box::new(RetryTransport::new(
+        inner,
+        RetryPolicy::from_config(config),
+    ));
+
+    if config.cache.enabled && cache_mode != CacheMode::Off {
+        let cache = ResponseCache::new(config)?;
+        return Ok(Box::new(CachingTransport::new(transport, cache, cache_mode)));
+    }
+    Ok(transport)
+}
+
+/// Response headers with values that are valid strings
+fn header_strings(headers: &reqwest::header::HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Convert string headers into a reqwest header map for direct requests
+fn to_header_map(headers: &HashMap<String, String>) -> Result<reqwest::header::HeaderMap> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            Ok((
+                reqwest::header::HeaderName::from_bytes(k.as_bytes())
+                    .with_context(|| format!("Invalid header name: {}", k))?,
+                reqwest::header::HeaderValue::from_str(v)
+                    .with_context(|| format!("Invalid value for header: {}", k))?,
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+//! A one-shot HTTP server for provider tests
+
+use super::{DirectTransport, Timeouts};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Answer one HTTP request on a local port with `status` and the body
+/// `chunks`, written one at a time. Returns the base URL and a handle
+/// yielding the raw request.
+pub async fn serve_once(
+    status: &'static str,
+    chunks: Vec<&'static str>,
+) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().to_string())
+                    })
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break;
+                }
+            }
+            if read == 0 {
+                break;
+            }
+        }
+
+        let head = format!("HTTP/1.1 {}\r\nconnection: close\r\n\r\n", status);
+        socket.write_all(head.as_bytes()).await.unwrap();
+        for chunk in chunks {
+            socket.write_all(chunk.as_bytes()).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        String::from_utf8_lossy(&request).to_string()
+    });
+    (url, handle)
+}
+
+/// The JSON body of a raw request captured by `serve_once`
+pub fn request_body(request: &str) -> serde_json::Value {
+    serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap()
+}
+
+/// A direct transport with the default timeouts
+pub fn transport() -> DirectTransport {
+    DirectTransport::new(Timeouts::default()).unwrap()
+}
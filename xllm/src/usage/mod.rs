@@ -0,0 +1,249 @@
+use crate::genconfig::{Config, PriceConfig};
+use crate::models::{Reply, Usage};
+use crate::utils::date::{format_unix_date, unix_now};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// One completed request in the usage ledger
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LedgerEntry {
+    /// Unix timestamp (seconds) of the request
+    pub timestamp: u64,
+    pub model: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+    /// Cost in USD, or None when no price was configured for the model
+    pub cost: Option<f64>,
+}
+
+/// Usage ledger (~/.local/share/xllm/usage.jsonl)
+pub fn get_ledger_path() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|p| p.join("xllm").join("usage.jsonl"))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine data directory"))
+}
+
+/// The `[prices]` entry for a model: an exact match, otherwise the longest
+/// key the model ID starts with (so `claude-sonnet-4` covers dated IDs)
+pub fn price_for<'a>(config: &'a Config, model: &str) -> Option<&'a PriceConfig> {
+    config.prices.get(model).or_else(|| {
+        config
+            .prices
+            .iter()
+            .filter(|(key, _)| model.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    })
+}
+
+/// Cost in USD of a request, if the model has a price
+pub fn cost_for(config: &Config, model: &str, usage: &Usage) -> Option<f64> {
+    price_for(config, model).map(|price| price.cost(usage))
+}
+
+/// Add a reply to the ledger and, with `--usage`, print its tokens and cost.
+/// `fallback_model` is used when the API did not report a model. Replies
+/// served from the response cache cost nothing and are not recorded.
+pub fn record_usage(
+    config: &Config,
+    reply: &Reply,
+    fallback_model: &str,
+    show: bool,
+    cached: bool,
+) {
+    let model = if reply.model.is_empty() {
+        fallback_model
+    } else {
+        reply.model.as_str()
+    };
+    if cached {
+        if show {
+            print_usage(&reply.usage, "cached, no cost".to_string());
+        }
+        return;
+    }
+
+    let cost = cost_for(config, model, &reply.usage);
+    if show {
+        let cost = match cost {
+            Some(cost) => format_cost(cost),
+            None => format!("no price for {} in [prices]", model),
+        };
+        print_usage(&reply.usage, cost);
+    }
+
+    let entry = LedgerEntry {
+        timestamp: unix_now(),
+        model: model.to_string(),
+        usage: reply.usage,
+        cost,
+    };
+    if let Err(e) = append_entry(&entry) {
+        eprintln!("⚠️  Failed to record usage: {}", e);
+    }
+}
+
+/// Print the token counts and cost of one response to stderr
+fn print_usage(usage: &Usage, cost: String) {
+    let mut parts = vec![
+        format!("{} in", format_count(usage.input_tokens)),
+        format!("{} out", format_count(usage.output_tokens)),
+    ];
+    if usage.cache_read_input_tokens > 0 {
+        parts.push(format!(
+            "{} cache read",
+            format_count(usage.cache_read_input_tokens)
+        ));
+    }
+    if usage.cache_creation_input_tokens > 0 {
+        parts.push(format!(
+            "{} cache write",
+            format_count(usage.cache_creation_input_tokens)
+        ));
+    }
+    eprintln!("📊 Tokens: {} · {}", parts.join(" · "), cost);
+}
+
+fn append_entry(entry: &LedgerEntry) -> Result<()> {
+    let path = get_ledger_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create data directory: {}", parent.display()))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open usage ledger: {}", path.display()))?;
+    let line = serde_json::to_string(entry).context("Failed to serialize usage entry")?;
+    writeln!(file, "{}", line)
+        .with_context(|| format!("Failed to write usage ledger: {}", path.display()))
+}
+
+/// Every entry in the ledger, oldest first
+pub fn read_ledger() -> Result<Vec<LedgerEntry>> {
+    let path = get_ledger_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read usage ledger: {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("⚠️  Skipping {} line {}: {}", path.display(), number + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Requests, tokens and cost for one day and model
+#[derive(Default)]
+struct Totals {
+    requests: u64,
+    usage: Usage,
+    cost: f64,
+    unpriced: u64,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &LedgerEntry) {
+        self.requests += 1;
+        self.usage += entry.usage;
+        match entry.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced += 1,
+        }
+    }
+}
+
+/// Print spend over the last `days` days, grouped by UTC day and model
+pub fn print_report(days: u64) -> Result<()> {
+    let since = unix_now().saturating_sub(days.saturating_mul(86_400));
+    let mut rows: BTreeMap<(String, String), Totals> = BTreeMap::new();
+    let mut total = Totals::default();
+
+    for entry in read_ledger()? {
+        if entry.timestamp < since {
+            continue;
+        }
+        let day = format_unix_date(entry.timestamp as i64);
+        rows.entry((day, entry.model.clone()))
+            .or_default()
+            .add(&entry);
+        total.add(&entry);
+    }
+
+    if rows.is_empty() {
+        println!("No usage recorded in the last {} days", days);
+        return Ok(());
+    }
+
+    println!(
+        "{:<10}  {:<32}  {:>6}  {:>12}  {:>12}  {:>12}  {:>10}",
+        "DATE", "MODEL", "REQS", "INPUT", "OUTPUT", "CACHED", "COST"
+    );
+    for ((day, model), totals) in &rows {
+        print_row(day, model, totals);
+    }
+    print_row("TOTAL", "", &total);
+
+    if total.unpriced > 0 {
+        eprintln!(
+            "⚠️  {} request(s) used models without a [prices] entry and are not included in the cost",
+            total.unpriced
+        );
+    }
+    Ok(())
+}
+
+fn print_row(day: &str, model: &str, totals: &Totals) {
+    let cached = totals.usage.cache_read_input_tokens + totals.usage.cache_creation_input_tokens;
+    let cost = if totals.requests == totals.unpriced {
+        "-".to_string()
+    } else {
+        format_cost(totals.cost)
+    };
+    println!(
+        "{:<10}  {:<32}  {:>6}  {:>12}  {:>12}  {:>12}  {:>10}",
+        day,
+        model,
+        totals.requests,
+        format_count(totals.usage.input_tokens),
+        format_count(totals.usage.output_tokens),
+        format_count(cached),
+        cost
+    );
+}
+
+/// Dollars with enough precision for single cheap requests
+fn format_cost(cost: f64) -> String {
+    if cost < 1.0 {
+        format!("${:.4}", cost)
+    } else {
+        format!("${:.2}", cost)
+    }
+}
+
+/// A token count with thousands separators, e.g. 12,345
+fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut result = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
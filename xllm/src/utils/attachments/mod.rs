@@ -0,0 +1,184 @@
+use crate::models::{ContentBlock, MediaSource};
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fs;
+use std::path::Path;
+
+/// Largest image the API accepts
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest request the API accepts. PDFs are sent base64-encoded, which grows
+/// them by a third, so about 24 MB is the largest PDF that fits.
+pub const MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// Most pages the API accepts in a single PDF
+pub const MAX_PDF_PAGES: usize = 100;
+
+/// Read an image file into a base64 `image` content block
+pub fn load_image(path: &str) -> Result<ContentBlock> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read image: {}", path))?;
+
+    let media_type = image_media_type(&bytes).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unsupported image format: {} (supported: PNG, JPEG, GIF, WebP)",
+            path
+        )
+    })?;
+
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow::anyhow!(
+            "Image {} is {}, larger than the {} limit",
+            path,
+            format_size(bytes.len()),
+            format_size(MAX_IMAGE_BYTES)
+        ));
+    }
+
+    eprintln!("🖼️  Attached {} ({}, {})", path, media_type, format_size(bytes.len()));
+
+    Ok(ContentBlock::Image {
+        source: MediaSource::base64(media_type, STANDARD.encode(&bytes)),
+    })
+}
+
+/// Read a PDF into a base64 `document` content block, titled with its file name
+pub fn load_pdf(path: &str) -> Result<ContentBlock> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read document: {}", path))?;
+
+    if !bytes.starts_with(b"%PDF-") {
+        return Err(anyhow::anyhow!("Not a PDF file: {}", path));
+    }
+
+    let encoded_len = base64_len(bytes.len());
+    if encoded_len > MAX_REQUEST_BYTES {
+        return Err(anyhow::anyhow!(
+            "Document {} is {} ({} base64-encoded), larger than the {} request limit",
+            path,
+            format_size(bytes.len()),
+            format_size(encoded_len),
+            format_size(MAX_REQUEST_BYTES)
+        ));
+    }
+
+    let pages = pdf_page_count(&bytes);
+    if let Some(pages) = pages.filter(|&pages| pages > MAX_PDF_PAGES) {
+        return Err(anyhow::anyhow!(
+            "Document {} has {} pages, more than the {} page limit",
+            path,
+            pages,
+            MAX_PDF_PAGES
+        ));
+    }
+
+    eprintln!(
+        "📄 Attached {} ({} pages, {})",
+        path,
+        pages.map_or_else(|| "?".to_string(), |pages| pages.to_string()),
+        format_size(bytes.len())
+    );
+
+    let title = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+
+    Ok(ContentBlock::Document {
+        source: MediaSource::base64("application/pdf", STANDARD.encode(&bytes)),
+        title,
+    })
+}
+
+/// Size of `len` bytes once base64-encoded
+fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Best-effort page count: the largest `/Count` in a page tree node, or the
+/// number of `/Type /Page` objects. Returns None when neither is visible,
+/// e.g. when the page tree lives in a compressed object stream.
+fn pdf_page_count(bytes: &[u8]) -> Option<usize> {
+    let count_re = regex::bytes::Regex::new(r"/Type\s*/Pages\b[^>]*?/Count\s+(\d+)").unwrap();
+    let page_re = regex::bytes::Regex::new(r"/Type\s*/Page\b").unwrap();
+
+    let tree_count = count_re
+        .captures_iter(bytes)
+        .filter_map(|caps| std::str::from_utf8(&caps[1]).ok()?.parse().ok())
+        .max();
+
+    tree_count.or_else(|| {
+        let pages = page_re.find_iter(bytes).count();
+        (pages > 0).then_some(pages)
+    })
+}
+
+/// Detect the image type from its magic bytes rather than trusting the extension
+fn image_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+pub fn format_size(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_media_type_comes_from_magic_bytes() {
+        assert_eq!(
+            image_media_type(b"\x89PNG\r\n\x1a\n...."),
+            Some("image/png")
+        );
+        assert_eq!(
+            image_media_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some("image/jpeg")
+        );
+        assert_eq!(image_media_type(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(
+            image_media_type(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(image_media_type(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(image_media_type(b"RIFF"), None);
+        assert_eq!(image_media_type(b"%PDF-1.7"), None);
+    }
+
+    #[test]
+    fn pdf_page_count_prefers_the_page_tree_count() {
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Pages /Kids [2 0 R] /Count 2 >>\n\
+            2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 12 >>\n\
+            3 0 obj << /Type /Page >>\n4 0 obj << /Type/Page >>";
+        assert_eq!(pdf_page_count(pdf), Some(12));
+    }
+
+    #[test]
+    fn pdf_page_count_falls_back_to_page_objects() {
+        let pdf = b"%PDF-1.4\n3 0 obj << /Type /Page >>\n4 0 obj << /Type/Page >>";
+        assert_eq!(pdf_page_count(pdf), Some(2));
+        assert_eq!(pdf_page_count(b"%PDF-1.5\n<< /Type /ObjStm >>"), None);
+    }
+
+    #[test]
+    fn base64_len_rounds_up_to_whole_quads() {
+        assert_eq!(base64_len(0), 0);
+        assert_eq!(base64_len(1), 4);
+        assert_eq!(base64_len(3), 4);
+        assert_eq!(base64_len(4), 8);
+        assert_eq!(base64_len(100), STANDARD.encode([0u8; 100]).len());
+    }
+}
@@ -0,0 +1,522 @@
+use crate::genconfig::Config;
+use anyhow::{Context, Result};
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+/// Per-project ignore file, read alongside `.gitignore`
+const IGNORE_FILENAME: &str = ".xllmignore";
+
+/// Default total size of `--file` context when `[global]` does not set one
+pub const DEFAULT_CONTEXT_MAX_BYTES: usize = 200_000;
+
+/// Limits on how much file content is attached to a prompt
+#[derive(Debug, Clone, Copy)]
+pub struct ContextBudget {
+    pub max_bytes: usize,
+    pub max_tokens: Option<usize>,
+}
+
+impl ContextBudget {
+    /// Read the budget from `[global]`, falling back to the defaults
+    pub fn from_config(config: &Config) -> Self {
+        match &config.global {
+            Some(global) => ContextBudget {
+                max_bytes: global.context_max_bytes,
+                max_tokens: global.context_max_tokens,
+            },
+            None => ContextBudget {
+                max_bytes: DEFAULT_CONTEXT_MAX_BYTES,
+                max_tokens: None,
+            },
+        }
+    }
+}
+
+/// A text file attached to the prompt
+#[derive(Debug, Clone)]
+pub struct ContextFile {
+    pub path: String,
+    pub language: &'static str,
+    pub content: String,
+}
+
+/// A file that matched but was left out, and why
+#[derive(Debug, Clone)]
+pub struct DroppedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// The files gathered from `--file` arguments
+#[derive(Debug, Clone, Default)]
+pub struct CollectedContext {
+    pub files: Vec<ContextFile>,
+    pub dropped: Vec<DroppedFile>,
+}
+
+impl CollectedContext {
+    pub fn total_bytes(&self) -> usize {
+        self.files.iter().map(|file| file.content.len()).sum()
+    }
+
+    /// Append every file to a prompt as a fenced block labeled with its language and path
+    pub fn append_to(&self, prompt: &str) -> String {
+        let mut result = prompt.to_string();
+        for file in &self.files {
+            let fence = fence_for(&file.content);
+            result.push_str(&format!(
+                "\n\n{}{} {}\n{}\n{}",
+                fence,
+                file.language,
+                file.path,
+                file.content.trim_end_matches('\n'),
+                fence
+            ));
+        }
+        result
+    }
+
+    /// Print what was included and what was dropped to stderr
+    pub fn print_report(&self) {
+        let total_bytes = self.total_bytes();
+        eprintln!(
+            "📎 Attached {} file(s), {} bytes (~{} tokens)",
+            self.files.len(),
+            total_bytes,
+            estimate_tokens(total_bytes)
+        );
+        for file in &self.files {
+            eprintln!("   + {} ({} bytes)", file.path, file.content.len());
+        }
+        for dropped in &self.dropped {
+            eprintln!("   - {} ({})", dropped.path, dropped.reason);
+        }
+    }
+}
+
+/// Gather files from paths, directories and glob patterns. Directories and
+/// globs respect `.gitignore` and `.xllmignore`; files named explicitly are
+/// always considered. Binary files are skipped and files that would exceed
+/// the budget are dropped.
+pub fn collect_files(patterns: &[String], budget: ContextBudget) -> Result<CollectedContext> {
+    let mut collected = CollectedContext::default();
+    let mut seen = HashSet::new();
+    let mut total_bytes = 0;
+
+    for pattern in patterns {
+        let paths = expand_pattern(pattern)?;
+        if paths.is_empty() {
+            collected.dropped.push(DroppedFile {
+                path: pattern.clone(),
+                reason: "no files matched".to_string(),
+            });
+        }
+
+        for path in paths {
+            let display = display_path(&path);
+            if !seen.insert(display.clone()) {
+                continue;
+            }
+
+            let bytes =
+                std::fs::read(&path).with_context(|| format!("Failed to read file: {}", display))?;
+            let content = match String::from_utf8(bytes) {
+                Ok(content) if !content.contains('\0') => content,
+                _ => {
+                    collected.dropped.push(DroppedFile {
+                        path: display,
+                        reason: "binary".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let new_total = total_bytes + content.len();
+            let over_tokens = budget
+                .max_tokens
+                .is_some_and(|max_tokens| estimate_tokens(new_total) > max_tokens);
+            if new_total > budget.max_bytes || over_tokens {
+                collected.dropped.push(DroppedFile {
+                    path: display,
+                    reason: format!("over budget, {} bytes", content.len()),
+                });
+                continue;
+            }
+
+            total_bytes = new_total;
+            collected.files.push(ContextFile {
+                language: language_for(&path),
+                path: display,
+                content,
+            });
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Rough token estimate used for the budget (about four bytes per token)
+pub fn estimate_tokens(bytes: usize) -> usize {
+    bytes.div_ceil(4)
+}
+
+/// Turn one `--file` argument into the files it refers to, sorted by path
+fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if path.is_dir() {
+        return Ok(walk(path, |_| true));
+    }
+    if !is_glob(pattern) {
+        return Err(anyhow::anyhow!("No such file or directory: {}", pattern));
+    }
+
+    let matcher = GlobBuilder::new(pattern.trim_start_matches("./"))
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Invalid glob pattern: {}", pattern))?
+        .compile_matcher();
+
+    Ok(walk(&glob_base(pattern), |path| {
+        matcher.is_match(display_path(path))
+    }))
+}
+
+/// Walk a directory honoring ignore files, keeping files accepted by `filter`
+fn walk(root: &Path, filter: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    WalkBuilder::new(root)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| filter(path))
+        .collect()
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
+}
+
+/// The directory a glob starts in: its leading components without wildcards
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if is_glob(&component.as_os_str().to_string_lossy()) {
+            break;
+        }
+        base.push(component);
+    }
+    if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    }
+}
+
+fn display_path(path: &Path) -> String {
+    let display = path.to_string_lossy();
+    display.strip_prefix("./").unwrap_or(&display).to_string()
+}
+
+/// A code fence longer than any backtick run inside the content
+fn fence_for(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Fence language for a file, based on its extension or name
+fn language_for(path: &Path) -> &'static str {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        "dockerfile" => return "dockerfile",
+        "makefile" => return "makefile",
+        _ => {}
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "sh" | "bash" | "zsh" => "bash",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "lua" => "lua",
+        "zig" => "zig",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "proto" => "protobuf",
+        _ => "text",
+    }
+}
+
+/// Append piped stdin to a prompt as a fenced context block
+pub fn append_stdin_content(prompt: &str, stdin_content: &str) -> String {
+    let fence = fence_for(stdin_content);
+    format!(
+        "{}\n\nPiped input:\n{}\n{}\n{}",
+        prompt, fence, stdin_content, fence
+    )
+}
+
+/// Read everything piped into stdin. With `explicit` (a `-` prompt) any
+/// non-terminal stdin is read; otherwise only a pipe or a redirected file is,
+/// so runs from CI, cron or a `while read` loop neither hang nor swallow
+/// input meant for something else. Returns None when nothing was read.
+pub fn read_piped_stdin(explicit: bool) -> Result<Option<String>> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() || !(explicit || stdin_is_pipe_or_file()) {
+        return Ok(None);
+    }
+
+    let mut input = String::new();
+    stdin
+        .read_to_string(&mut input)
+        .context("Failed to read from stdin")?;
+
+    let input = input.trim_end_matches(['\n', '\r']);
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(input.to_string()))
+    }
+}
+
+/// Whether stdin is a FIFO or a file redirected from its start, rather than
+/// /dev/null, a socket, or a file a `while read` loop is partway through
+#[cfg(unix)]
+fn stdin_is_pipe_or_file() -> bool {
+    use std::io::Seek;
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(mut file) = std::io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(std::fs::File::from)
+    else {
+        return false;
+    };
+    match file.metadata() {
+        Ok(metadata) if metadata.file_type().is_fifo() => true,
+        Ok(metadata) if metadata.is_file() => file.stream_position().is_ok_and(|pos| pos == 0),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn stdin_is_pipe_or_file() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fence_is_longer_than_any_backtick_run() {
+        assert_eq!(fence_for("plain text"), "```");
+        assert_eq!(fence_for("inline `code` and ``more``"), "```");
+        assert_eq!(fence_for("```rust\nfn main() {}\n```"), "````");
+        assert_eq!(fence_for("`````"), "``````");
+    }
+
+    #[test]
+    fn glob_base_stops_at_the_first_wildcard() {
+        assert_eq!(glob_base("src/**/*.rs"), PathBuf::from("src"));
+        assert_eq!(glob_base("src/utils/*.rs"), PathBuf::from("src/utils"));
+        assert_eq!(glob_base("src/{a,b}/mod.rs"), PathBuf::from("src"));
+        assert_eq!(glob_base("*.toml"), PathBuf::from("."));
+        assert_eq!(glob_base("/etc/*.conf"), PathBuf::from("/etc"));
+    }
+
+    #[test]
+    fn language_comes_from_extension_or_name() {
+        assert_eq!(language_for(Path::new("src/main.rs")), "rust");
+        assert_eq!(language_for(Path::new("Dockerfile")), "dockerfile");
+        assert_eq!(language_for(Path::new("app.TSX")), "tsx");
+    }
+
+    /// A fresh directory under the system temp dir holding `files`
+    fn temp_tree(test: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("xllm-context-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&root);
+        for (name, content) in files {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    fn arg(root: &Path, pattern: &str) -> String {
+        root.join(pattern).to_string_lossy().to_string()
+    }
+
+    fn unlimited() -> ContextBudget {
+        ContextBudget {
+            max_bytes: DEFAULT_CONTEXT_MAX_BYTES,
+            max_tokens: None,
+        }
+    }
+
+    fn paths(root: &Path, files: &[ContextFile]) -> Vec<String> {
+        let prefix = format!("{}/", root.display());
+        files
+            .iter()
+            .map(|file| file.path.strip_prefix(&prefix).unwrap().to_string())
+            .collect()
+    }
+
+    fn dropped(root: &Path, collected: &CollectedContext) -> Vec<(String, String)> {
+        let prefix = format!("{}/", root.display());
+        collected
+            .dropped
+            .iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(&prefix).unwrap_or(&file.path);
+                (path.to_string(), file.reason.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ignore_files_exclude_walked_files_but_not_named_ones() {
+        let root = temp_tree(
+            "ignore",
+            &[
+                (".gitignore", b"target/\n"),
+                (".xllmignore", b"*.lock\n"),
+                ("src/main.rs", b"fn main() {}\n"),
+                ("target/out.rs", b"// generated\n"),
+                ("Cargo.lock", b"# lock\n"),
+            ],
+        );
+
+        let walked = collect_files(&[root.display().to_string()], unlimited()).unwrap();
+        assert_eq!(paths(&root, &walked.files), ["src/main.rs"]);
+
+        let globbed = collect_files(&[arg(&root, "**/*.rs")], unlimited()).unwrap();
+        assert_eq!(paths(&root, &globbed.files), ["src/main.rs"]);
+
+        let named = collect_files(&[arg(&root, "Cargo.lock")], unlimited()).unwrap();
+        assert_eq!(paths(&root, &named.files), ["Cargo.lock"]);
+    }
+
+    #[test]
+    fn binary_files_are_skipped() {
+        let root = temp_tree(
+            "binary",
+            &[
+                ("image.png", b"\x89PNG\r\n\x1a\n\xff\xfe"),
+                ("nul.txt", b"text\0more"),
+                ("notes.txt", b"plain text\n"),
+            ],
+        );
+
+        let collected = collect_files(&[root.display().to_string()], unlimited()).unwrap();
+        assert_eq!(paths(&root, &collected.files), ["notes.txt"]);
+        assert_eq!(
+            dropped(&root, &collected),
+            [
+                ("image.png".to_string(), "binary".to_string()),
+                ("nul.txt".to_string(), "binary".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_over_the_budget_are_dropped_with_the_reason() {
+        let root = temp_tree(
+            "budget",
+            &[
+                ("a.txt", b"12345\n"),
+                ("b.txt", b"123456\n"),
+                ("c.txt", b"12\n"),
+            ],
+        );
+        let files = [
+            arg(&root, "a.txt"),
+            arg(&root, "b.txt"),
+            arg(&root, "c.txt"),
+        ];
+
+        let by_bytes = ContextBudget {
+            max_bytes: 10,
+            max_tokens: None,
+        };
+        let collected = collect_files(&files, by_bytes).unwrap();
+        assert_eq!(paths(&root, &collected.files), ["a.txt", "c.txt"]);
+        assert_eq!(
+            dropped(&root, &collected),
+            [("b.txt".to_string(), "over budget, 7 bytes".to_string())]
+        );
+
+        // 6 + 7 bytes is 4 tokens, 6 + 3 bytes is 3
+        let by_tokens = ContextBudget {
+            max_bytes: DEFAULT_CONTEXT_MAX_BYTES,
+            max_tokens: Some(3),
+        };
+        let collected = collect_files(&files, by_tokens).unwrap();
+        assert_eq!(paths(&root, &collected.files), ["a.txt", "c.txt"]);
+        assert_eq!(
+            dropped(&root, &collected),
+            [("b.txt".to_string(), "over budget, 7 bytes".to_string())]
+        );
+    }
+
+    #[test]
+    fn overlapping_patterns_attach_each_file_once() {
+        let root = temp_tree("overlap", &[("a.rs", b"// a\n"), ("b.rs", b"// b\n")]);
+
+        let collected = collect_files(
+            &[
+                arg(&root, "b.rs"),
+                arg(&root, "*.rs"),
+                root.display().to_string(),
+            ],
+            unlimited(),
+        )
+        .unwrap();
+        assert_eq!(paths(&root, &collected.files), ["b.rs", "a.rs"]);
+        assert!(collected.dropped.is_empty());
+    }
+}
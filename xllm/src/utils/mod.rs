@@ -1,2 +1,7 @@
+pub mod attachments;
+pub mod context;
+pub mod date;
 pub mod proxy;
 pub mod render;
+pub mod retry;
+pub mod sse;
@@ -1,12 +1,13 @@
 use crate::genconfig::Config;
-use crate::models::claude::{ClaudeRequest, Message};
+use crate::transport::{HttpRequest, HttpResponse, Timeouts, Transport};
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use async_trait::async_trait;
 use tokio::net::TcpStream;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use aes_gcm::{Aes256Gcm, Key, KeyInit};
 use aes_gcm::aead::{Aead, OsRng, AeadCore};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 // Pre-shared encryption key - must match proxy server
 const OBFUSCATION_KEY: &[u8; 32] = b"xllm_secure_proxy_key_2024_v1.0!";
@@ -17,31 +18,11 @@ struct ProxyRequest {
     request_object: Vec<u8>, // Encrypted HTTP request data
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct HttpRequest {
-    method: String,
-    url: String,
-    headers: HashMap<String, String>,
-    body: Vec<u8>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct HttpResponse {
-    status_code: u16,
-    headers: HashMap<String, String>,
-    body: Vec<u8>,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 struct ProxyResponse {
     response_object: Vec<u8>, // Encrypted HTTP response data
 }
 
-/// Determines if we should use proxy based on config
-pub fn proxy_config_true(config: &Config) -> bool {
-    config.global.as_ref().map_or(false, |global| global.proxy)
-}
-
 /// Gets the proxy URL from config, returns error if proxy is enabled but URL is missing
 pub fn get_proxy_url(config: &Config) -> Result<Option<String>> {
     match &config.global {
@@ -57,70 +38,72 @@ pub fn get_proxy_url(config: &Config) -> Result<Option<String>> {
     }
 }
 
-/// Call Claude API through TCP proxy with encryption
-pub async fn call_claude_via_tcp_proxy(
-    claude_config: &crate::genconfig::ClaudeConfig,
-    global_config: &Config,
-    prompt: &str,
-    model_override: Option<crate::models::claude::ClaudeModels>,
-    max_tokens_override: Option<u32>,
-) -> Result<String> {
-    let model = if let Some(model_enum) = model_override {
-        model_enum.to_string()
-    } else {
-        claude_config.model.clone()
-    };
+/// Sends requests through the xllm-proxy TCP server with AES-256-GCM encryption
+pub struct TcpProxyTransport {
+    proxy_url: String,
+    timeouts: Timeouts,
+}
 
-    let max_tokens = max_tokens_override.unwrap_or(claude_config.max_tokens);
+impl TcpProxyTransport {
+    pub fn new(proxy_url: String, timeouts: Timeouts) -> Self {
+        TcpProxyTransport { proxy_url, timeouts }
+    }
+}
 
-    let request = ClaudeRequest {
-        model,
-        max_tokens,
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }],
-    };
+#[async_trait]
+impl Transport for TcpProxyTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let exchange = send_via_tcp_proxy(&self.proxy_url, request, self.timeouts.connect);
+        tokio::time::timeout(self.timeouts.total, exchange)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Request through TCP proxy timed out after {}s",
+                    self.timeouts.total.as_secs()
+                )
+            })??
+            .error_for_status()
+    }
 
-    // Prepare headers for the API request
-    let mut headers = HashMap::new();
-    headers.insert("Content-Type".to_string(), "application/json".to_string());
-    headers.insert("x-api-key".to_string(), claude_config.anthropic_api_key.clone());
-    headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
-
-    // Serialize the request to JSON
-    let body = serde_json::to_vec(&request).context("Failed to serialize Claude request")?;
-
-    // Create the internal HTTP request structure
-    let http_request = HttpRequest {
-        method: "POST".to_string(),
-        url: format!("{}/v1/messages", claude_config.url),
-        headers,
-        body,
-    };
+    /// The proxy buffers whole upstream responses, so it never streams
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+}
 
+/// Encrypt an HTTP request, send it through the TCP proxy and decrypt the response
+async fn send_via_tcp_proxy(
+    proxy_url: &str,
+    http_request: &HttpRequest,
+    connect_timeout: Duration,
+) -> Result<HttpResponse> {
     // Encrypt the HTTP request
-    let encrypted_request = encrypt_request_object(&http_request)?;
+    let encrypted_request = encrypt_request_object(http_request)?;
 
-    // Get proxy URL and extract host/port
-    let proxy_url = get_proxy_url(global_config)?
-        .ok_or_else(|| anyhow::anyhow!("Proxy URL not configured"))?;
+    // Extract host/port from the proxy URL
+    let proxy_addr = parse_proxy_url(proxy_url)?;
 
-    let proxy_addr = parse_proxy_url(&proxy_url)?;
-
-    println!("📡 Connecting to TCP proxy: {}", proxy_addr);
+    eprintln!("📡 Connecting to TCP proxy: {}", proxy_addr);
 
     // Create the obfuscated proxy request - only proxy URL visible
     let proxy_request = ProxyRequest {
-        proxy_url: proxy_url.clone(), // Only this is visible in network traffic
+        proxy_url: proxy_url.to_string(), // Only this is visible in network traffic
         request_object: encrypted_request,  // Fully encrypted binary data
     };
 
-    // Connect to proxy server
-    let mut stream = TcpStream::connect(&proxy_addr).await
+    // Connect to proxy server. A timeout is reported as an I/O error so it
+    // is retried like other connection failures.
+    let mut stream = tokio::time::timeout(connect_timeout, TcpStream::connect(&proxy_addr))
+        .await
+        .unwrap_or_else(|_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {}s", connect_timeout.as_secs()),
+            ))
+        })
         .context("Failed to connect to TCP proxy")?;
 
-    println!("🔒 Sending encrypted request via TCP (Anthropic URL, API keys, and data fully hidden)");
+    eprintln!("🔒 Sending encrypted request via TCP (provider URL, API keys, and data fully hidden)");
 
     // Send the encrypted request
     let request_data = serde_json::to_vec(&proxy_request)
@@ -146,26 +129,10 @@ pub async fn call_claude_via_tcp_proxy(
     let http_response = decrypt_response_object(&proxy_response.response_object)
         .context("Failed to decrypt response from proxy")?;
 
-    println!("✅ Successfully received and decrypted response from TCP proxy");
-    println!("📊 Response status: {}", http_response.status_code);
-
-    // Check if the response was successful
-    if http_response.status_code < 200 || http_response.status_code >= 300 {
-        let error_text = String::from_utf8_lossy(&http_response.body);
-        return Err(anyhow::anyhow!("API request failed with status {}: {}", http_response.status_code, error_text));
-    }
-
-    // Parse the response body as JSON
-    let claude_response: crate::models::claude::ClaudeResponse = 
-        serde_json::from_slice(&http_response.body)
-            .context("Failed to parse Claude API response")?;
+    eprintln!("✅ Successfully received and decrypted response from TCP proxy");
+    eprintln!("📊 Response status: {}", http_response.status_code);
 
-    // Extract text from the first content block
-    if let Some(content_block) = claude_response.content.first() {
-        Ok(content_block.text.clone())
-    } else {
-        Err(anyhow::anyhow!("No content in Claude response"))
-    }
+    Ok(http_response)
 }
 
 fn encrypt_request_object(http_request: &HttpRequest) -> Result<Vec<u8>> {
@@ -205,7 +172,7 @@ fn decrypt_response_object(encrypted_data: &[u8]) -> Result<HttpResponse> {
     Ok(http_response)
 }
 
-fn parse_proxy_url(proxy_url: &str) -> Result<String> {
+pub fn parse_proxy_url(proxy_url: &str) -> Result<String> {
     // Parse URL like "http://learn.hydrafusion.dev:50051" to "learn.hydrafusion.dev:50051"
     let url = url::Url::parse(proxy_url)
         .context("Invalid proxy URL format")?;
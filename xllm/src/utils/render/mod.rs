@@ -1,6 +1,57 @@
+use crate::models::{Reply, Usage};
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+use std::sync::Mutex;
 use termimad::crossterm::style::Color::*;
 use termimad::*;
 
+/// How the final reply is written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Rendered for the terminal
+    Markdown,
+    /// The reply text exactly as received
+    Raw,
+    /// A JSON object with the text, model, usage and stop reason
+    Json,
+}
+
+impl OutputFormat {
+    /// Parse `--output`, defaulting to markdown on a terminal and raw text otherwise
+    pub fn from_arg(arg: Option<&str>, stdout_is_terminal: bool) -> Self {
+        match arg {
+            Some("markdown") => OutputFormat::Markdown,
+            Some("raw") => OutputFormat::Raw,
+            Some("json") => OutputFormat::Json,
+            _ if stdout_is_terminal => OutputFormat::Markdown,
+            _ => OutputFormat::Raw,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    model: &'a str,
+    text: &'a str,
+    stop_reason: Option<&'a str>,
+    usage: Usage,
+    session_id: &'a str,
+}
+
+/// Print a reply as a single JSON object
+pub fn print_json_reply(reply: &Reply, session_id: &str) -> Result<()> {
+    let output = JsonOutput {
+        model: &reply.model,
+        text: &reply.text,
+        stop_reason: reply.stop_reason.as_deref(),
+        usage: reply.usage,
+        session_id,
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
 /// Terminmad method
 pub fn render_markdown(text: &str) {
     let mut skin = MadSkin::default();
@@ -19,3 +70,36 @@ pub fn render_markdown(text: &str) {
     let formatted_text = skin.area_text(text, &area);
     print!("{}", formatted_text);
 }
+
+/// The most recent spinner, so lower layers can report progress on it
+static ACTIVE_SPINNER: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Spinner shown on stderr while waiting on a model response.
+/// It stays hidden when stderr is not a terminal.
+pub fn new_spinner() -> ProgressBar {
+    let spinner = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["🤖", "🔧", "⚙️", "🔩", "🤖", "⚡", "💻", "🧠"])
+            .template("{spinner} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message("loading...");
+    spinner.enable_steady_tick(std::time::Duration::from_millis(200));
+    if let Ok(mut active) = ACTIVE_SPINNER.lock() {
+        *active = Some(spinner.clone());
+    }
+    spinner
+}
+
+/// Show a status message on the spinner, or on stderr once the spinner is
+/// gone or hidden
+pub fn show_status(message: &str) {
+    let active = ACTIVE_SPINNER.lock().ok().and_then(|active| active.clone());
+    match active {
+        Some(spinner) if !spinner.is_finished() && !spinner.is_hidden() => {
+            spinner.set_message(message.to_string())
+        }
+        _ => eprintln!("{}", message),
+    }
+}
@@ -0,0 +1,417 @@
+use crate::genconfig::Config;
+use crate::transport::{HttpRequest, HttpResponse, StatusError, Transport};
+use crate::utils::render::show_status;
+use anyhow::Result;
+use async_trait::async_trait;
+use rand::Rng;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 1_000;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 30_000;
+
+/// Statuses worth retrying: rate limits, server errors and Anthropic's 529 overloaded
+const RETRYABLE_STATUSES: &[u16] = &[429, 500, 502, 503, 504, 529];
+
+/// How often and how long to wait before retrying a failed request
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Read the policy from `[global]`, falling back to the defaults
+    pub fn from_config(config: &Config) -> Self {
+        let (max_retries, base_ms, max_ms) = match &config.global {
+            Some(global) => (
+                global.max_retries,
+                global.retry_base_delay_ms,
+                global.retry_max_delay_ms,
+            ),
+            None => (
+                DEFAULT_MAX_RETRIES,
+                DEFAULT_RETRY_BASE_DELAY_MS,
+                DEFAULT_RETRY_MAX_DELAY_MS,
+            ),
+        };
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(base_ms),
+            max_delay: Duration::from_millis(max_ms),
+        }
+    }
+
+    /// Exponential backoff with jitter for the given retry (1-based). A
+    /// server-provided `retry-after` wins over the computed delay, capped at
+    /// `max_delay` so a large value cannot stall the command.
+    fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay);
+        // Keep half the delay and randomize the rest so clients spread out
+        let half = exponential / 2;
+        half + half.mul_f64(rand::thread_rng().r#gen::<f64>())
+    }
+}
+
+/// Why a failed request may be retried, and how long the server asked us to wait
+struct Retryable {
+    reason: String,
+    retry_after: Option<Duration>,
+}
+
+/// Classify an error from a transport. Returns None for errors that would
+/// fail again, such as bad requests or authentication problems.
+fn retryable(error: &anyhow::Error) -> Option<Retryable> {
+    if let Some(status) = error.downcast_ref::<StatusError>() {
+        if !RETRYABLE_STATUSES.contains(&status.status_code) {
+            return None;
+        }
+        let reason = match status.status_code {
+            429 => "Rate limited (429)".to_string(),
+            529 => "API overloaded (529)".to_string(),
+            code => format!("Server error ({})", code),
+        };
+        return Some(Retryable {
+            reason,
+            retry_after: retry_after(status),
+        });
+    }
+
+    // A request that ran into the overall timeout would only time out again
+    let connection_error = error.chain().any(|cause| {
+        cause.downcast_ref::<std::io::Error>().is_some()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect() || (e.is_request() && !e.is_timeout()))
+    });
+    connection_error.then(|| Retryable {
+        reason: "Connection error".to_string(),
+        retry_after: None,
+    })
+}
+
+/// The wait requested by `retry-after-ms` or `retry-after` (in seconds)
+fn retry_after(status: &StatusError) -> Option<Duration> {
+    if let Some(ms) = status
+        .header("retry-after-ms")
+        .and_then(|value| value.trim().parse::<f64>().ok())
+    {
+        return Duration::try_from_secs_f64(ms / 1000.0).ok();
+    }
+    status
+        .header("retry-after")
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// Wraps another transport and retries failed requests according to a
+/// `RetryPolicy`. Used for both direct and proxied requests.
+pub struct RetryTransport {
+    inner: Box<dyn Transport>,
+    policy: RetryPolicy,
+}
+
+impl RetryTransport {
+    pub fn new(inner: Box<dyn Transport>, policy: RetryPolicy) -> Self {
+        RetryTransport { inner, policy }
+    }
+
+    /// Wait before the next attempt if `error` can be retried, otherwise
+    /// hand the error back
+    async fn backoff(&self, error: anyhow::Error, retry: u32) -> Result<()> {
+        let Some(retryable) = retryable(&error) else {
+            return Err(error);
+        };
+        if retry > self.policy.max_retries {
+            return Err(anyhow::anyhow!(
+                "{:#} (gave up after {} retries)",
+                error,
+                self.policy.max_retries
+            ));
+        }
+
+        let delay = self.policy.delay(retry, retryable.retry_after);
+        show_status(&format!(
+            "⏳ {}, retrying in {:.1}s ({}/{})",
+            retryable.reason,
+            delay.as_secs_f64(),
+            retry,
+            self.policy.max_retries
+        ));
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transport for RetryTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let mut retry = 1;
+        loop {
+            match self.inner.send(request).await {
+                Ok(response) => return Ok(response),
+                Err(error) => self.backoff(error, retry).await?,
+            }
+            retry += 1;
+        }
+    }
+
+    async fn send_streaming(
+        &self,
+        request: &HttpRequest,
+        on_chunk: &mut (dyn for<'a> FnMut(&'a [u8]) -> Result<()> + Send),
+    ) -> Result<()> {
+        let mut retry = 1;
+        loop {
+            // Once part of the response has been handed on, a retry would
+            // repeat it, so only failures before the first chunk are retried
+            let mut received = false;
+            let result = self
+                .inner
+                .send_streaming(request, &mut |chunk| {
+                    received = true;
+                    on_chunk(chunk)
+                })
+                .await;
+            match result {
+                Ok(()) => return Ok(()),
+                Err(error) if received => return Err(error),
+                Err(error) => self.backoff(error, retry).await?,
+            }
+            retry += 1;
+        }
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn status(code: u16, headers: &[(&str, &str)]) -> anyhow::Error {
+        StatusError {
+            status_code: code,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: String::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn retries_rate_limits_overloads_and_server_errors() {
+        for code in [429, 500, 502, 503, 504, 529] {
+            assert!(retryable(&status(code, &[])).is_some(), "{}", code);
+        }
+        assert_eq!(
+            retryable(&status(529, &[])).unwrap().reason,
+            "API overloaded (529)"
+        );
+        assert_eq!(
+            retryable(&status(429, &[])).unwrap().reason,
+            "Rate limited (429)"
+        );
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        for code in [400, 401, 403, 404, 413, 501] {
+            assert!(retryable(&status(code, &[])).is_none(), "{}", code);
+        }
+        assert!(retryable(&anyhow::anyhow!("Failed to parse response")).is_none());
+    }
+
+    #[test]
+    fn retries_io_errors_anywhere_in_the_chain() {
+        let error = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+            .context("Failed to reach the proxy");
+        assert_eq!(retryable(&error).unwrap().reason, "Connection error");
+    }
+
+    #[test]
+    fn reads_retry_after_headers() {
+        let wait = |headers: &[(&str, &str)]| retryable(&status(429, headers)).unwrap().retry_after;
+        assert_eq!(wait(&[("Retry-After", "2")]), Some(Duration::from_secs(2)));
+        assert_eq!(
+            wait(&[("retry-after", " 1.5 ")]),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            wait(&[("retry-after", "9"), ("retry-after-ms", "250")]),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(
+            wait(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            None
+        );
+        assert_eq!(wait(&[("retry-after", "-1")]), None);
+        assert_eq!(wait(&[]), None);
+    }
+
+    #[test]
+    fn delay_backs_off_exponentially_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(1_000),
+            max_delay: Duration::from_millis(5_000),
+        };
+        for (retry, full) in [(1, 1_000), (2, 2_000), (3, 4_000), (4, 5_000), (30, 5_000)] {
+            let delay = policy.delay(retry, None);
+            let full = Duration::from_millis(full);
+            assert!(
+                delay >= full / 2 && delay <= full,
+                "retry {}: {:?}",
+                retry,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn delay_honours_retry_after_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(1_000),
+            max_delay: Duration::from_millis(5_000),
+        };
+        assert_eq!(
+            policy.delay(4, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(86_400))),
+            Duration::from_secs(5)
+        );
+    }
+
+    /// Replays scripted results and counts attempts
+    struct Scripted {
+        results: Mutex<VecDeque<Result<HttpResponse>>>,
+        attempts: Arc<AtomicU32>,
+        /// Chunk handed on before each streaming attempt
+        partial: Option<&'static [u8]>,
+    }
+
+    #[async_trait]
+    impl Transport for Scripted {
+        async fn send(&self, _request: &HttpRequest) -> Result<HttpResponse> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            self.results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("unexpected attempt")
+        }
+
+        async fn send_streaming(
+            &self,
+            request: &HttpRequest,
+            on_chunk: &mut (dyn for<'a> FnMut(&'a [u8]) -> Result<()> + Send),
+        ) -> Result<()> {
+            if let Some(chunk) = self.partial {
+                on_chunk(chunk)?;
+            }
+            let response = self.send(request).await?;
+            on_chunk(&response.body)
+        }
+    }
+
+    fn ok() -> Result<HttpResponse> {
+        Ok(HttpResponse {
+            status_code: 200,
+            headers: HashMap::new(),
+            body: b"done".to_vec(),
+        })
+    }
+
+    /// A retrying transport over `results`, without delays, and its attempt count
+    fn retrying(
+        results: Vec<Result<HttpResponse>>,
+        partial: Option<&'static [u8]>,
+    ) -> (RetryTransport, Arc<AtomicU32>) {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let scripted = Scripted {
+            results: Mutex::new(results.into()),
+            attempts: attempts.clone(),
+            partial,
+        };
+        let policy = RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        };
+        (RetryTransport::new(Box::new(scripted), policy), attempts)
+    }
+
+    fn request() -> HttpRequest {
+        HttpRequest::get("http://localhost/".to_string(), HashMap::new())
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (transport, attempts) = retrying(
+            vec![
+                Err(status(529, &[])),
+                Err(status(429, &[("retry-after-ms", "1")])),
+                ok(),
+            ],
+            None,
+        );
+        let response = transport.send(&request()).await.unwrap();
+        assert_eq!(response.body, b"done");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (transport, attempts) = retrying(
+            vec![
+                Err(status(503, &[])),
+                Err(status(503, &[])),
+                Err(status(503, &[])),
+            ],
+            None,
+        );
+        let error = transport.send(&request()).await.unwrap_err();
+        assert!(error.to_string().ends_with("(gave up after 2 retries)"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_a_bad_request() {
+        let (transport, attempts) = retrying(vec![Err(status(400, &[]))], None);
+        assert!(transport.send(&request()).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_a_stream_that_already_started() {
+        let (transport, attempts) = retrying(vec![Err(status(529, &[])), ok()], Some(b"partial"));
+
+        let mut received = Vec::new();
+        let result = transport
+            .send_streaming(&request(), &mut |chunk| {
+                received.extend_from_slice(chunk);
+                Ok(())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(received, b"partial");
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,65 @@
+/// Incremental decoder for a `text/event-stream` body.
+/// Bytes are buffered until a blank line terminates an event, and the joined
+/// `data:` lines of each complete event are returned.
+#[derive(Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        // Events are plain text, so carriage returns only ever appear in line endings
+        self.buffer.extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let raw: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let raw = String::from_utf8_lossy(&raw);
+            let data = raw
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|value| value.strip_prefix(' ').unwrap_or(value))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !data.is_empty() {
+                events.push(data);
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_data_of_complete_events() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.push(b"event: ping\ndata: {\"a\":1}\n\ndata: two\n\n");
+        assert_eq!(events, ["{\"a\":1}", "two"]);
+    }
+
+    #[test]
+    fn buffers_events_split_across_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: hel").is_empty());
+        assert!(decoder.push(b"lo\n").is_empty());
+        assert_eq!(decoder.push(b"\ndata: next"), ["hello"]);
+        assert_eq!(decoder.push(b"\n\n"), ["next"]);
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: one\r\n\r").is_empty());
+        assert_eq!(decoder.push(b"\ndata: two\r\n\r\n"), ["one", "two"]);
+    }
+
+    #[test]
+    fn joins_multiline_data_and_skips_events_without_data() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.push(b": comment\n\ndata: first\ndata:second\n\n");
+        assert_eq!(events, ["first\nsecond"]);
+    }
+}